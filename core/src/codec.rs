@@ -0,0 +1,108 @@
+//! Integration with [`tokio_util::codec`].
+use crate::packet::Packet;
+use crate::traits::AsyncPacketQueueT;
+use bytes::BytesMut;
+use std::io;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+/// Codec for raw packets.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PacketCodec;
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Packet>> {
+        match src.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(src.split().freeze())),
+        }
+    }
+}
+
+impl Encoder<Packet> for PacketCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Packet, dst: &mut BytesMut) -> io::Result<()> {
+        dst.extend_from_slice(&item);
+        Ok(())
+    }
+}
+
+/// Adapts a codec to packet boundaries of a queue. Created by [`framed`].
+///
+/// Inner decoder is given exactly one packet at a time. Bytes of the packet, that are not
+/// consumed by the decoder, are discarded, so a packet, that the decoder produces no item from,
+/// is skipped. Read buffer is kept large enough for the largest packet.
+#[derive(Debug, Clone)]
+pub struct PacketFramer<C> {
+    inner: C,
+    max_packet_size: usize,
+}
+
+impl<C> PacketFramer<C> {
+    pub fn new(inner: C, max_packet_size: usize) -> Self {
+        Self {
+            inner,
+            max_packet_size,
+        }
+    }
+
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: Decoder> Decoder for PacketFramer<C> {
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<C::Item>, C::Error> {
+        if src.is_empty() {
+            src.reserve(self.max_packet_size);
+            return Ok(None);
+        }
+
+        let mut packet = src.split();
+        src.reserve(self.max_packet_size);
+        // Leftover bytes are dropped together with `packet`
+        self.inner.decode(&mut packet)
+    }
+}
+
+impl<I, C: Encoder<I>> Encoder<I> for PacketFramer<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), C::Error> {
+        self.inner.encode(item, dst)
+    }
+}
+
+/// Wraps the queue into [`Framed`], so that every frame is exactly one packet.
+///
+/// TUN/TAP queues transfer one whole packet per read or write, so the codec is never given
+/// a partial packet, and encoded packets are written one by one, instead of being merged.
+pub fn framed<Q, C>(queue: Q, codec: C) -> io::Result<Framed<Q, PacketFramer<C>>>
+where
+    Q: AsyncPacketQueueT + AsyncRead + AsyncWrite,
+{
+    let max_packet_size = queue.max_packet_size()?;
+    let mut framed = Framed::with_capacity(
+        queue,
+        PacketFramer::new(codec, max_packet_size),
+        max_packet_size,
+    );
+    // Buffered packet is written before the next one is encoded, so packets are never merged
+    framed.set_backpressure_boundary(1);
+    Ok(framed)
+}
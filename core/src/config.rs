@@ -1,4 +1,5 @@
 use crate::traits::PlatformIfConfigT;
+use crate::Error;
 use derive_builder::Builder;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
@@ -11,8 +12,13 @@ pub enum Layer {
 }
 
 #[derive(Builder)]
+#[builder(build_fn(validate = "Self::validate", error = "Error"))]
 pub struct IfConfig<P: PlatformIfConfigT> {
     /// Interface name on Unix and interface alias on Windows.
+    ///
+    /// On Linux it can be a template, like `tun%d`, where `%d` is replaced with the lowest
+    /// free number, when the interface is created. Empty name is the same as `tun%d` or `tap%d`.
+    /// Resolved name is returned by the interface.
     pub name: String,
     /// Interface type: TUN or TAP.
     #[builder(default = "Layer::default()")]
@@ -34,4 +40,11 @@ impl<P: PlatformIfConfigT> IfConfigBuilder<P> {
         self.platform = Some(f(builder)?);
         Ok(self)
     }
+
+    fn validate(&self) -> Result<(), Error> {
+        match &self.name {
+            Some(name) => P::validate_name(name),
+            None => Ok(()),
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use crate::config::Layer;
-use std::io;
+use std::path::PathBuf;
+use std::{fmt, io};
 use thiserror::Error as ThisError;
 
 #[non_exhaustive]
@@ -27,6 +28,76 @@ pub enum Error {
     },
     #[error("layer is unsupported: {0:?}")]
     LayerUnsupported(Layer),
+    #[error("interface not found: {0}")]
+    InterfaceNotFound(String),
+    #[error("interface layer mismatch: expected {expected:?}, found {actual:?}")]
+    LayerMismatch { expected: Layer, actual: Layer },
+    #[error(
+        "TUN/TAP device node {} is missing, load the `tun` kernel module or create the node",
+        .path.display()
+    )]
+    DeviceNodeMissing { path: PathBuf },
+    #[error(
+        "permission denied: {operation}{}",
+        .capability.map(|c| format!(", {c} is required")).unwrap_or_default()
+    )]
+    PermissionDenied {
+        operation: String,
+        /// Capability, that grants the permission, if there is one
+        capability: Option<&'static str>,
+    },
+    #[error("interface name is already in use: {0}")]
+    NameInUse(String),
+    #[error("{operation} failed: {source}")]
+    OperationFailed {
+        /// System call or ioctl, that failed
+        operation: &'static str,
+        source: io::Error,
+    },
+}
+
+/// Packet does not fit into the read buffer.
+///
+/// It is returned inside [`io::Error`] of [`io::ErrorKind::InvalidInput`] kind, instead of
+/// silently truncating the packet. The packet itself is dropped.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PacketTooLarge {
+    /// Size of the packet, if it is reported by the platform
+    pub required: Option<usize>,
+    /// Size of the read buffer
+    pub capacity: usize,
+}
+
+impl PacketTooLarge {
+    /// Extracts this error from an [`io::Error`], returned by a read.
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for PacketTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.required {
+            Some(required) => write!(
+                f,
+                "packet is too large for the buffer: {required} > {}",
+                self.capacity
+            ),
+            None => write!(
+                f,
+                "packet is too large for the buffer of {} bytes",
+                self.capacity
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PacketTooLarge {}
+
+impl From<PacketTooLarge> for io::Error {
+    fn from(err: PacketTooLarge) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
 }
 
 impl From<io::Error> for Error {
@@ -35,6 +106,35 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err)
+            | Error::NetConfigError(netconfig::Error::Io(err))
+            | Error::OperationFailed { source: err, .. } => err,
+            err @ Error::DeviceNodeMissing { .. } => {
+                io::Error::new(io::ErrorKind::NotFound, err.to_string())
+            }
+            err @ Error::PermissionDenied { .. } => {
+                io::Error::new(io::ErrorKind::PermissionDenied, err.to_string())
+            }
+            err @ Error::NameInUse(_) => io::Error::new(io::ErrorKind::AddrInUse, err.to_string()),
+            // Not every error is Send + Sync, so only the message is kept
+            err => io::Error::new(io::ErrorKind::Other, err.to_string()),
+        }
+    }
+}
+
+impl From<derive_builder::UninitializedFieldError> for Error {
+    fn from(err: derive_builder::UninitializedFieldError) -> Self {
+        Error::InvalidConfigValue {
+            name: err.field_name().to_string(),
+            value: String::new(),
+            reason: "value is required".to_string(),
+        }
+    }
+}
+
 impl From<netconfig::Error> for Error {
     fn from(err: netconfig::Error) -> Self {
         Error::NetConfigError(err)
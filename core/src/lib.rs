@@ -1,7 +1,10 @@
+#[cfg(all(unix, feature = "codec"))]
+pub mod codec;
 pub mod config;
 mod error;
+pub mod packet;
 #[cfg(unix)]
 pub mod queue;
 pub mod traits;
 
-pub use error::Error;
+pub use error::{Error, PacketTooLarge};
@@ -0,0 +1,180 @@
+use crate::traits::AsyncPacketQueueT;
+use bytes::BytesMut;
+use futures::{Sink, Stream};
+use std::cell::RefCell;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Owned buffer, containing exactly one packet
+pub type Packet = bytes::Bytes;
+
+thread_local! {
+    /// Buffer for reads of single packets, reused by all queues of the thread
+    static SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Calls `f` with a scratch buffer of `size` bytes.
+///
+/// Packet is read into the scratch buffer and copied into a buffer of its exact size, so
+/// a received packet does not hold memory for the largest possible packet.
+pub(crate) fn with_scratch<R>(size: usize, f: impl FnOnce(&mut [u8]) -> R) -> R {
+    SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+        Ok(mut scratch) => {
+            if scratch.len() < size {
+                scratch.resize(size, 0);
+            }
+            f(&mut scratch[..size])
+        }
+        // Nested read from inside of another one
+        Err(_) => f(&mut vec![0u8; size]),
+    })
+}
+
+pub(crate) fn check_written(written: usize, packet: &[u8]) -> io::Result<()> {
+    match written == packet.len() {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::WriteZero,
+            format!("packet is written partially: {written} of {}", packet.len()),
+        )),
+    }
+}
+
+/// Future for [`AsyncPacketQueueT::recv_packet`]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct RecvPacket<'a, Q: ?Sized> {
+    queue: &'a mut Q,
+}
+
+impl<'a, Q: AsyncPacketQueueT + ?Sized> RecvPacket<'a, Q> {
+    pub(crate) fn new(queue: &'a mut Q) -> Self {
+        Self { queue }
+    }
+}
+
+impl<Q: AsyncPacketQueueT + ?Sized> Future for RecvPacket<'_, Q> {
+    type Output = io::Result<Packet>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let size = this.queue.max_packet_size()?;
+        with_scratch(size, |buf| {
+            let n = ready!(Pin::new(&mut *this.queue).poll_read(cx, buf))?;
+            Poll::Ready(Ok(Packet::copy_from_slice(&buf[..n])))
+        })
+    }
+}
+
+/// Stream of received packets and sink of packets to send, created by
+/// [`AsyncPacketQueueT::into_stream`].
+///
+/// Packets are read into a reused buffer, sized by [`AsyncPacketQueueT::max_packet_size`],
+/// and copied into buffers of their exact size.
+pub struct PacketStream<Q> {
+    queue: Q,
+    read_buf: BytesMut,
+    write_packet: Option<Packet>,
+}
+
+impl<Q: AsyncPacketQueueT> PacketStream<Q> {
+    pub(crate) fn new(queue: Q) -> Self {
+        Self {
+            queue,
+            read_buf: BytesMut::new(),
+            write_packet: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &Q {
+        &self.queue
+    }
+
+    pub fn get_mut(&mut self) -> &mut Q {
+        &mut self.queue
+    }
+
+    /// Returns the underlying queue. Packet, that is not flushed yet, is dropped.
+    pub fn into_inner(self) -> Q {
+        self.queue
+    }
+
+    fn poll_write_packet(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let Some(packet) = &self.write_packet {
+            let n = ready!(Pin::new(&mut self.queue).poll_write(cx, packet))?;
+            let packet = self.write_packet.take().unwrap();
+            check_written(n, &packet)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<Q: AsyncPacketQueueT> Stream for PacketStream<Q> {
+    type Item = io::Result<Packet>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        let size = this.queue.max_packet_size()?;
+        if this.read_buf.len() < size {
+            this.read_buf.resize(size, 0);
+        }
+
+        let n = ready!(Pin::new(&mut this.queue).poll_read(cx, &mut this.read_buf[..size]))?;
+        if n == 0 {
+            return Poll::Ready(None);
+        }
+        Poll::Ready(Some(Ok(Packet::copy_from_slice(&this.read_buf[..n]))))
+    }
+}
+
+impl<Q: AsyncPacketQueueT> Sink<Packet> for PacketStream<Q> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_packet(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Packet) -> io::Result<()> {
+        self.get_mut().write_packet = Some(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_packet(cx))?;
+        Pin::new(&mut this.queue).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_packet(cx))?;
+        Pin::new(&mut this.queue).poll_close(cx)
+    }
+}
+
+/// Future for [`AsyncPacketQueueT::send_packet`]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SendPacket<'a, Q: ?Sized> {
+    queue: &'a mut Q,
+    packet: &'a [u8],
+}
+
+impl<'a, Q: AsyncPacketQueueT + ?Sized> SendPacket<'a, Q> {
+    pub(crate) fn new(queue: &'a mut Q, packet: &'a [u8]) -> Self {
+        Self { queue, packet }
+    }
+}
+
+impl<Q: AsyncPacketQueueT + ?Sized> Future for SendPacket<'_, Q> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let n = ready!(Pin::new(&mut *this.queue).poll_write(cx, this.packet))?;
+        Poll::Ready(check_written(n, this.packet))
+    }
+}
@@ -0,0 +1,117 @@
+use crate::queue::syncfd::SyncFdQueue;
+use crate::queue::FdQueueT;
+use crate::traits::AsyncQueueT;
+use crate::Error;
+use async_io::Async;
+use delegate::delegate;
+use futures::{AsyncRead, AsyncWrite};
+use std::io::{self, IoSlice, IoSliceMut};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Queue, driven by [async-io](https://docs.rs/async-io) reactor.
+///
+/// Works with any runtime, that is built on top of async-io, like smol and async-std.
+pub struct AsyncIoFdQueue {
+    inner: Async<SyncFdQueue>,
+    /// Error of a batch read, that is reported by the next batch read
+    pending_error: Option<io::Error>,
+}
+
+impl AsyncQueueT for AsyncIoFdQueue {}
+
+impl FdQueueT for AsyncIoFdQueue {
+    const BLOCKING: bool = false;
+
+    fn new(device: OwnedFd) -> Result<Self, Error> {
+        let inner =
+            Async::new(SyncFdQueue::new(device)?).map_err(|source| Error::OperationFailed {
+                operation: "async-io reactor registration",
+                source,
+            })?;
+        Ok(Self {
+            inner,
+            pending_error: None,
+        })
+    }
+}
+
+impl AsyncIoFdQueue {
+    /// Receives multiple packets in a single readiness cycle.
+    ///
+    /// Waits until at least one packet is available, then reads packets until the queue is drained
+    /// or all `bufs` are filled. Size of each packet is stored in `sizes` at the same index.
+    /// Returns number of received packets.
+    ///
+    /// If reading fails after some packets are received, these packets are returned and
+    /// the error is reported by the next call.
+    pub async fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        if bufs.is_empty() || sizes.is_empty() {
+            return Ok(0);
+        }
+
+        let pending_error = &mut self.pending_error;
+        self.inner
+            .read_with_mut(|inner| inner.read_batch(bufs, sizes, pending_error))
+            .await
+    }
+
+    /// Sends multiple packets, waiting for readiness only when the queue is full.
+    ///
+    /// Returns number of sent packets. If writing fails after some packets are sent,
+    /// the error is discarded and the number of sent packets is returned.
+    pub async fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        let mut sent = 0;
+
+        while sent < packets.len() {
+            match self
+                .inner
+                .write_with_mut(|inner| inner.write_batch(&packets[sent..]))
+                .await
+            {
+                Ok(n) => sent += n,
+                Err(e) if sent == 0 => return Err(e),
+                Err(_) => break,
+            }
+        }
+
+        Ok(sent)
+    }
+}
+
+impl AsRawFd for AsyncIoFdQueue {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for AsyncIoFdQueue {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl AsyncRead for AsyncIoFdQueue {
+    delegate! {
+        to Pin::new(&mut self.inner) {
+            fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+            fn poll_read_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &mut [IoSliceMut<'_>]) -> Poll<io::Result<usize>>;
+        }
+    }
+}
+
+impl AsyncWrite for AsyncIoFdQueue {
+    delegate! {
+        to Pin::new(&mut self.inner) {
+            fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+            fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<io::Result<usize>>;
+            fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+            fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+        }
+    }
+}
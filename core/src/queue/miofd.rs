@@ -0,0 +1,111 @@
+use crate::queue::syncfd::SyncFdQueue;
+use crate::queue::FdQueueT;
+use crate::traits::SyncQueueT;
+use crate::Error;
+use delegate::delegate;
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+
+/// Non-blocking queue, that can be registered in a [mio](https://docs.rs/mio) [`Registry`].
+///
+/// Reads and writes fail with [`io::ErrorKind::WouldBlock`], until the queue is reported ready.
+pub struct MioFdQueue(SyncFdQueue);
+
+impl SyncQueueT for MioFdQueue {}
+
+impl FdQueueT for MioFdQueue {
+    const BLOCKING: bool = false;
+
+    fn new(device: OwnedFd) -> Result<Self, Error> {
+        Ok(Self(SyncFdQueue::new(device)?))
+    }
+}
+
+impl MioFdQueue {
+    /// Receives multiple packets at once, until the queue is drained or all `bufs` are filled.
+    ///
+    /// Size of each packet is stored in `sizes` at the same index. Returns number of received packets.
+    /// Fails with [`io::ErrorKind::WouldBlock`] only if no packets are available.
+    ///
+    /// If reading fails after some packets are received, these packets are returned and
+    /// the error is reported by the next call.
+    pub fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        self.0.recv_queued(bufs, sizes)
+    }
+
+    /// Sends multiple packets at once, until all are sent or the queue is full.
+    ///
+    /// Returns number of sent packets. Fails with [`io::ErrorKind::WouldBlock`] only if
+    /// no packets are sent.
+    pub fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        self.0.write_batch(packets)
+    }
+}
+
+impl Read for MioFdQueue {
+    delegate! {
+        to self.0 {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+            fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize>;
+        }
+    }
+}
+
+impl Write for MioFdQueue {
+    delegate! {
+        to self.0 {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+            fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize>;
+            fn flush(&mut self) -> io::Result<()>;
+        }
+    }
+}
+
+impl Source for MioFdQueue {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+impl AsRawFd for MioFdQueue {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for MioFdQueue {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl IntoRawFd for MioFdQueue {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
@@ -1,11 +1,18 @@
-use std::os::unix::io::OwnedFd;
+use crate::Error;
+use std::os::unix::io::{AsRawFd, OwnedFd};
 
+#[cfg(feature = "async-io")]
+pub mod asynciofd;
+#[cfg(feature = "mio")]
+pub mod miofd;
 pub mod syncfd;
 #[cfg(feature = "tokio")]
 pub mod tokiofd;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uringfd;
 
-pub trait FdQueueT {
+pub trait FdQueueT: AsRawFd + Sized {
     const BLOCKING: bool;
 
-    fn new(device: OwnedFd) -> Self;
+    fn new(device: OwnedFd) -> Result<Self, Error>;
 }
@@ -1,41 +1,340 @@
 use crate::queue::FdQueueT;
 use crate::traits::SyncQueueT;
+use crate::{Error, PacketTooLarge};
 use delegate::delegate;
 use std::fs;
-use std::io::{self, Read, Write};
-use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
 
-pub struct SyncFdQueue(fs::File);
+pub struct SyncFdQueue {
+    file: fs::File,
+    /// Error of a batch read, that is reported by the next batch read
+    pending_error: Option<io::Error>,
+}
 
 impl SyncQueueT for SyncFdQueue {}
 
 impl FdQueueT for SyncFdQueue {
     const BLOCKING: bool = true;
 
-    fn new(device: OwnedFd) -> Self {
-        Self(device.into())
+    fn new(device: OwnedFd) -> Result<Self, Error> {
+        Ok(Self {
+            file: device.into(),
+            pending_error: None,
+        })
+    }
+}
+
+/// Reads exactly one packet, failing with [`PacketTooLarge`] if it does not fit into `bufs`
+#[cfg(not(target_os = "macos"))]
+fn read_packet(mut file: &fs::File, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+    let capacity = bufs.iter().map(|buf| buf.len()).sum();
+
+    // TUN driver silently truncates packets to the buffer size, so one spare byte is read
+    // to detect it
+    let mut spare = [0u8; 1];
+    let n = match bufs {
+        [buf] => file.read_vectored(&mut [IoSliceMut::new(buf), IoSliceMut::new(&mut spare)])?,
+        _ => {
+            let mut with_spare: Vec<_> = bufs.iter_mut().map(|buf| IoSliceMut::new(buf)).collect();
+            with_spare.push(IoSliceMut::new(&mut spare));
+            file.read_vectored(&mut with_spare)?
+        }
+    };
+    if n > capacity {
+        return Err(PacketTooLarge {
+            required: None,
+            capacity,
+        }
+        .into());
+    }
+    Ok(n)
+}
+
+/// Reads exactly one packet, failing with [`PacketTooLarge`] if it does not fit into `bufs`
+#[cfg(target_os = "macos")]
+fn read_packet(file: &fs::File, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+    let capacity = bufs.iter().map(|buf| buf.len()).sum();
+
+    // utun is a datagram socket, so truncation is reported only via recvmsg flags
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    // IoSliceMut is ABI-compatible with iovec
+    msg.msg_iov = bufs.as_mut_ptr() as *mut libc::iovec;
+    msg.msg_iovlen = bufs.len() as _;
+
+    let n = unsafe { libc::recvmsg(file.as_raw_fd(), &mut msg, 0) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if msg.msg_flags & libc::MSG_TRUNC != 0 {
+        return Err(PacketTooLarge {
+            required: None,
+            capacity,
+        }
+        .into());
+    }
+    Ok(n as usize)
+}
+
+/// Reads packets from a non-blocking descriptor, until `bufs` are filled or read would block.
+///
+/// Error of a previous batch is reported first. If reading fails after some packets are
+/// received, the error is stored in `pending_error`.
+fn read_batch<B: AsMut<[u8]>>(
+    file: &fs::File,
+    bufs: &mut [B],
+    sizes: &mut [usize],
+    pending_error: &mut Option<io::Error>,
+) -> io::Result<usize> {
+    if let Some(e) = pending_error.take() {
+        return Err(e);
+    }
+
+    let count = bufs.len().min(sizes.len());
+    let mut received = 0;
+
+    while received < count {
+        match read_packet(file, &mut [IoSliceMut::new(bufs[received].as_mut())]) {
+            Ok(n) => {
+                sizes[received] = n;
+                received += 1;
+            }
+            Err(e) if received == 0 => return Err(e),
+            Err(e) => {
+                defer_error(e, pending_error);
+                break;
+            }
+        }
+    }
+
+    Ok(received)
+}
+
+/// Keeps an error, that ended a batch, for the next batch.
+///
+/// Packet may be already consumed (e.g. truncated), so the error must not be lost.
+/// Drained queue and interrupted reads lose nothing, so they are dropped.
+fn defer_error(e: io::Error, pending_error: &mut Option<io::Error>) {
+    if !matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    ) {
+        *pending_error = Some(e);
+    }
+}
+
+fn set_nonblocking(file: &fs::File, nonblocking: bool) -> io::Result<()> {
+    let mut value = nonblocking as libc::c_int;
+    match unsafe { libc::ioctl(file.as_raw_fd(), libc::FIONBIO, &mut value) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
     }
 }
 
 impl Read for SyncFdQueue {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read_packet(&self.file, &mut [IoSliceMut::new(buf)])
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        read_packet(&self.file, bufs)
+    }
+}
+
+impl Write for SyncFdQueue {
     delegate! {
-        to self.0 {
-            fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error>;
+        to self.file {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+            fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize>;
+            fn flush(&mut self) -> io::Result<()>;
         }
     }
 }
 
-impl Write for SyncFdQueue {
+// Every read and write transfers a whole packet, so the queue can be shared between a reader and
+// a writer
+impl Read for &SyncFdQueue {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        read_packet(&self.file, &mut [IoSliceMut::new(buf)])
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        read_packet(&self.file, bufs)
+    }
+}
+
+impl Write for &SyncFdQueue {
     delegate! {
-        to self.0 {
+        to (&self.file) {
             fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+            fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize>;
             fn flush(&mut self) -> io::Result<()>;
         }
     }
 }
 
+impl SyncFdQueue {
+    /// Receives multiple packets at once.
+    ///
+    /// Blocks until at least one packet is available, then reads packets, that are already queued,
+    /// until all `bufs` are filled. Size of each packet is stored in `sizes` at the same index.
+    /// Returns number of received packets.
+    ///
+    /// Queued packets are read with the descriptor switched to non-blocking mode, until
+    /// the queue is drained.
+    ///
+    /// If reading fails after some packets are received, these packets are returned and
+    /// the error is reported by the next call.
+    pub fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        let count = bufs.len().min(sizes.len());
+        if count == 0 {
+            return Ok(0);
+        }
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+
+        sizes[0] = read_packet(&self.file, &mut [IoSliceMut::new(bufs[0].as_mut())])?;
+        if count == 1 {
+            return Ok(1);
+        }
+
+        if let Err(e) = set_nonblocking(&self.file, true) {
+            defer_error(e, &mut self.pending_error);
+            return Ok(1);
+        }
+        let drained = read_batch(
+            &self.file,
+            &mut bufs[1..count],
+            &mut sizes[1..count],
+            &mut self.pending_error,
+        );
+        let restored = set_nonblocking(&self.file, false);
+
+        let received = match drained {
+            Ok(n) => 1 + n,
+            Err(e) => {
+                defer_error(e, &mut self.pending_error);
+                1
+            }
+        };
+        if let Err(e) = restored {
+            // Descriptor stays non-blocking, so the next batch must not block on it silently
+            self.pending_error = Some(e);
+        }
+        Ok(received)
+    }
+
+    /// Sends multiple packets at once.
+    ///
+    /// Returns number of sent packets. If writing fails after some packets are sent,
+    /// the error is discarded and the number of sent packets is returned.
+    pub fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        self.write_batch(packets)
+    }
+
+    /// Reads packets from a non-blocking queue, that is not shared
+    #[cfg(feature = "mio")]
+    pub(crate) fn recv_queued<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        read_batch(&self.file, bufs, sizes, &mut self.pending_error)
+    }
+
+    /// Reads packets from a non-blocking queue, that is shared by reader and writer.
+    ///
+    /// Error, that ends a batch, is kept by the reader in `pending_error`.
+    #[cfg(any(feature = "tokio", feature = "async-io"))]
+    pub(crate) fn read_batch<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+        pending_error: &mut Option<io::Error>,
+    ) -> io::Result<usize> {
+        read_batch(&self.file, bufs, sizes, pending_error)
+    }
+
+    /// Writes packets until all are sent or write fails
+    pub(crate) fn write_batch<B: AsRef<[u8]>>(&self, packets: &[B]) -> io::Result<usize> {
+        let mut queue = self;
+        for (sent, packet) in packets.iter().enumerate() {
+            match queue.write(packet.as_ref()) {
+                Ok(_) => {}
+                Err(e) if sent == 0 => return Err(e),
+                Err(_) => return Ok(sent),
+            }
+        }
+        Ok(packets.len())
+    }
+}
+
 impl AsRawFd for SyncFdQueue {
     fn as_raw_fd(&self) -> RawFd {
-        self.0.as_raw_fd()
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for SyncFdQueue {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl IntoRawFd for SyncFdQueue {
+    fn into_raw_fd(self) -> RawFd {
+        self.file.into_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn oversized_packet() {
+        // Datagram sockets keep packet boundaries and truncate reads, the same as TUN devices
+        let (peer, device) = UnixDatagram::pair().unwrap();
+        let mut queue = SyncFdQueue::new(OwnedFd::from(device)).unwrap();
+        peer.send(&[1; 100]).unwrap();
+        peer.send(&[2; 10]).unwrap();
+
+        let mut buf = [0u8; 50];
+        let e = queue.read(&mut buf).unwrap_err();
+        let err = PacketTooLarge::from_io_error(&e).unwrap();
+        assert_eq!(err.capacity, 50);
+
+        assert_eq!(queue.read(&mut buf).unwrap(), 10);
+        assert_eq!(buf[..10], [2; 10]);
+    }
+
+    #[test]
+    fn batch_error_is_deferred() {
+        let (peer, device) = UnixDatagram::pair().unwrap();
+        let mut queue = SyncFdQueue::new(OwnedFd::from(device)).unwrap();
+        for len in [10, 100, 20] {
+            peer.send(&vec![0; len]).unwrap();
+        }
+
+        let mut bufs = [[0u8; 50]; 4];
+        let mut sizes = [0; 4];
+        assert_eq!(queue.recv_batch(&mut bufs, &mut sizes).unwrap(), 1);
+        assert_eq!(sizes[0], 10);
+
+        let e = queue.recv_batch(&mut bufs, &mut sizes).unwrap_err();
+        assert!(PacketTooLarge::from_io_error(&e).is_some());
+
+        assert_eq!(queue.recv_batch(&mut bufs, &mut sizes).unwrap(), 1);
+        assert_eq!(sizes[0], 20);
+
+        // Descriptor is blocking again after the queue is drained
+        let flags = unsafe { libc::fcntl(queue.as_raw_fd(), libc::F_GETFL) };
+        assert_eq!(flags & libc::O_NONBLOCK, 0);
     }
 }
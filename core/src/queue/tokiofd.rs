@@ -1,15 +1,41 @@
+use crate::packet::check_written;
 use crate::queue::syncfd::SyncFdQueue;
 use crate::queue::FdQueueT;
 use crate::traits::AsyncQueueT;
-use futures::{AsyncRead, AsyncWrite};
-use std::io::{self, Read, Write};
-use std::os::unix::io::OwnedFd;
+use crate::Error;
+use futures::{AsyncRead, AsyncWrite, FutureExt};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{ready, Context, Poll};
 use tokio::io::unix::AsyncFd;
+use tokio::io::ReadBuf;
 
 pub struct TokioFdQueue {
-    inner: AsyncFd<SyncFdQueue>,
+    inner: Arc<AsyncFd<SyncFdQueue>>,
+    /// Error of a batch read, that is reported by the next batch read
+    pending_error: Option<io::Error>,
+}
+
+/// Reading half of a [`TokioFdQueue`], created by [`TokioFdQueue::into_split`].
+pub struct ReadHalf {
+    inner: Arc<AsyncFd<SyncFdQueue>>,
+    pending_error: Option<io::Error>,
+}
+
+/// Writing half of a [`TokioFdQueue`], created by [`TokioFdQueue::into_split`].
+pub struct WriteHalf {
+    inner: Arc<AsyncFd<SyncFdQueue>>,
+}
+
+/// Cloneable handle for sending packets from multiple tasks, created by [`TokioFdQueue::sender`].
+///
+/// Every packet is written by a single write, so packets from different tasks are never
+/// interleaved.
+#[derive(Clone)]
+pub struct PacketSender {
+    inner: Arc<AsyncFd<SyncFdQueue>>,
 }
 
 impl AsyncQueueT for TokioFdQueue {}
@@ -17,70 +43,408 @@ impl AsyncQueueT for TokioFdQueue {}
 impl FdQueueT for TokioFdQueue {
     const BLOCKING: bool = false;
 
-    fn new(device: OwnedFd) -> Self {
-        Self {
-            inner: AsyncFd::new(SyncFdQueue::new(device)).unwrap(),
+    fn new(device: OwnedFd) -> Result<Self, Error> {
+        let inner =
+            AsyncFd::new(SyncFdQueue::new(device)?).map_err(|source| Error::OperationFailed {
+                operation: "tokio reactor registration",
+                source,
+            })?;
+        Ok(Self {
+            inner: Arc::new(inner),
+            pending_error: None,
+        })
+    }
+}
+
+impl TokioFdQueue {
+    /// Splits the queue into reading and writing halves, that can be used from different tasks.
+    ///
+    /// Both halves share the same file descriptor, which is closed when both of them are dropped.
+    pub fn into_split(self) -> (ReadHalf, WriteHalf) {
+        (
+            ReadHalf {
+                inner: self.inner.clone(),
+                pending_error: self.pending_error,
+            },
+            WriteHalf { inner: self.inner },
+        )
+    }
+
+    /// Creates a cloneable handle for sending packets to this queue.
+    ///
+    /// Sender keeps the file descriptor open, even if the queue is dropped.
+    pub fn sender(&self) -> PacketSender {
+        PacketSender {
+            inner: self.inner.clone(),
         }
     }
+
+    /// Receives multiple packets in a single readiness cycle.
+    ///
+    /// Waits until at least one packet is available, then reads packets until the queue is drained
+    /// or all `bufs` are filled. Size of each packet is stored in `sizes` at the same index.
+    /// Returns number of received packets.
+    ///
+    /// If reading fails after some packets are received, these packets are returned and
+    /// the error is reported by the next call.
+    pub async fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        recv_batch(&self.inner, bufs, sizes, &mut self.pending_error).await
+    }
+
+    /// Sends multiple packets, waiting for readiness only when the queue is full.
+    ///
+    /// Returns number of sent packets. If writing fails after some packets are sent,
+    /// the error is discarded and the number of sent packets is returned.
+    pub async fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        send_batch(&self.inner, packets).await
+    }
 }
 
-impl AsyncRead for TokioFdQueue {
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut [u8],
-    ) -> Poll<io::Result<usize>> {
-        let self_mut = self.get_mut();
-        loop {
-            let mut guard = ready!(self_mut.inner.poll_read_ready_mut(cx))?;
+impl ReadHalf {
+    /// See [`TokioFdQueue::recv_batch`]
+    pub async fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        recv_batch(&self.inner, bufs, sizes, &mut self.pending_error).await
+    }
+}
 
-            match guard.try_io(|inner| inner.get_mut().read(buf)) {
-                Ok(Ok(n)) => {
-                    return Poll::Ready(Ok(n));
-                }
-                Ok(Err(e)) => return Poll::Ready(Err(e)),
-                Err(_) => continue,
-            }
+impl WriteHalf {
+    /// See [`TokioFdQueue::sender`]
+    pub fn sender(&self) -> PacketSender {
+        PacketSender {
+            inner: self.inner.clone(),
         }
     }
+
+    /// See [`TokioFdQueue::send_batch`]
+    pub async fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        send_batch(&self.inner, packets).await
+    }
 }
 
-impl AsyncWrite for TokioFdQueue {
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<io::Result<usize>> {
-        let self_mut = self.get_mut();
+impl PacketSender {
+    /// Sends a single packet, waiting until the queue is writable.
+    pub async fn send(&self, packet: &[u8]) -> io::Result<()> {
+        // `writable` registers a waker per call, so concurrent senders are all woken up
         loop {
-            let mut guard = ready!(self_mut.inner.poll_write_ready_mut(cx))?;
+            let mut guard = self.inner.writable().await?;
 
-            match guard.try_io(|inner| inner.get_mut().write(buf)) {
-                Ok(Ok(n)) => {
-                    return Poll::Ready(Ok(n));
-                }
-                Ok(Err(e)) => return Poll::Ready(Err(e)),
+            match guard.try_io(|inner| {
+                let mut queue = inner.get_ref();
+                queue.write(packet)
+            }) {
+                Ok(written) => return check_written(written?, packet),
                 Err(_) => continue,
             }
         }
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        let self_mut = self.get_mut();
-        loop {
-            let mut guard = ready!(self_mut.inner.poll_write_ready_mut(cx))?;
+    /// Sends a single packet without waiting.
+    ///
+    /// Fails with [`io::ErrorKind::WouldBlock`], if the queue is not writable.
+    pub fn try_send(&self, packet: &[u8]) -> io::Result<()> {
+        let written = try_write_io(&self.inner, |mut inner| inner.write(packet))?;
+        check_written(written, packet)
+    }
+}
 
-            match guard.try_io(|inner| inner.get_mut().flush()) {
-                Ok(Ok(())) => {
-                    return Poll::Ready(Ok(()));
-                }
-                Ok(Err(e)) => return Poll::Ready(Err(e)),
-                Err(_) => continue,
+async fn recv_batch<B: AsMut<[u8]>>(
+    inner: &AsyncFd<SyncFdQueue>,
+    bufs: &mut [B],
+    sizes: &mut [usize],
+    pending_error: &mut Option<io::Error>,
+) -> io::Result<usize> {
+    if bufs.is_empty() || sizes.is_empty() {
+        return Ok(0);
+    }
+    // Reported without waiting for readiness
+    if let Some(e) = pending_error.take() {
+        return Err(e);
+    }
+
+    loop {
+        let mut guard = inner.readable().await?;
+
+        match guard.try_io(|inner| inner.get_ref().read_batch(bufs, sizes, pending_error)) {
+            Ok(result) => return result,
+            Err(_) => continue,
+        }
+    }
+}
+
+async fn send_batch<B: AsRef<[u8]>>(
+    inner: &AsyncFd<SyncFdQueue>,
+    packets: &[B],
+) -> io::Result<usize> {
+    let mut sent = 0;
+
+    while sent < packets.len() {
+        let mut guard = inner.writable().await?;
+
+        match guard.try_io(|inner| inner.get_ref().write_batch(&packets[sent..])) {
+            Ok(Ok(n)) => sent += n,
+            Ok(Err(e)) if sent == 0 => return Err(e),
+            Ok(Err(_)) => break,
+            Err(_) => continue,
+        }
+    }
+
+    Ok(sent)
+}
+
+fn poll_read_io<R>(
+    inner: &AsyncFd<SyncFdQueue>,
+    cx: &mut Context<'_>,
+    mut f: impl FnMut(&SyncFdQueue) -> io::Result<R>,
+) -> Poll<io::Result<R>> {
+    loop {
+        let mut guard = ready!(inner.poll_read_ready(cx))?;
+
+        match guard.try_io(|inner| f(inner.get_ref())) {
+            Ok(result) => return Poll::Ready(result),
+            Err(_) => continue,
+        }
+    }
+}
+
+fn poll_write_io<R>(
+    inner: &AsyncFd<SyncFdQueue>,
+    cx: &mut Context<'_>,
+    mut f: impl FnMut(&SyncFdQueue) -> io::Result<R>,
+) -> Poll<io::Result<R>> {
+    loop {
+        let mut guard = ready!(inner.poll_write_ready(cx))?;
+
+        match guard.try_io(|inner| f(inner.get_ref())) {
+            Ok(result) => return Poll::Ready(result),
+            Err(_) => continue,
+        }
+    }
+}
+
+/// Performs `f` if the queue is readable, clearing readiness if `f` would block
+fn try_read_io<R>(
+    inner: &AsyncFd<SyncFdQueue>,
+    f: impl FnOnce(&SyncFdQueue) -> io::Result<R>,
+) -> io::Result<R> {
+    match inner.readable().now_or_never() {
+        Some(guard) => match guard?.try_io(|inner| f(inner.get_ref())) {
+            Ok(result) => result,
+            Err(_) => Err(io::ErrorKind::WouldBlock.into()),
+        },
+        None => Err(io::ErrorKind::WouldBlock.into()),
+    }
+}
+
+/// Performs `f` if the queue is writable, clearing readiness if `f` would block
+fn try_write_io<R>(
+    inner: &AsyncFd<SyncFdQueue>,
+    f: impl FnOnce(&SyncFdQueue) -> io::Result<R>,
+) -> io::Result<R> {
+    match inner.writable().now_or_never() {
+        Some(guard) => match guard?.try_io(|inner| f(inner.get_ref())) {
+            Ok(result) => result,
+            Err(_) => Err(io::ErrorKind::WouldBlock.into()),
+        },
+        None => Err(io::ErrorKind::WouldBlock.into()),
+    }
+}
+
+macro_rules! impl_read {
+    ($t:ty) => {
+        impl $t {
+            /// Waits for a packet to become available for reading.
+            ///
+            /// Readiness can be spurious, so [`try_read`](Self::try_read) may still fail with
+            /// [`io::ErrorKind::WouldBlock`].
+            pub async fn readable(&self) -> io::Result<()> {
+                self.inner.readable().await.map(|_| ())
+            }
+
+            /// Tries to read a packet without waiting.
+            ///
+            /// Fails with [`io::ErrorKind::WouldBlock`], if no packet is available.
+            pub fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+                try_read_io(&self.inner, |mut inner| inner.read(buf))
+            }
+
+            /// Like [`try_read`](Self::try_read), but reads into multiple buffers.
+            pub fn try_read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+                try_read_io(&self.inner, |mut inner| inner.read_vectored(bufs))
+            }
+        }
+
+        impl AsyncRead for $t {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                poll_read_io(&self.inner, cx, |mut inner| inner.read(buf))
+            }
+
+            fn poll_read_vectored(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                bufs: &mut [IoSliceMut<'_>],
+            ) -> Poll<io::Result<usize>> {
+                poll_read_io(&self.inner, cx, |mut inner| inner.read_vectored(bufs))
+            }
+        }
+
+        impl tokio::io::AsyncRead for $t {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                let n = ready!(poll_read_io(&self.inner, cx, |mut inner| inner
+                    .read(buf.initialize_unfilled())))?;
+                buf.advance(n);
+                Poll::Ready(Ok(()))
+            }
+        }
+    };
+}
+
+macro_rules! impl_write {
+    ($t:ty) => {
+        impl $t {
+            /// Waits for the queue to become writable.
+            ///
+            /// Readiness can be spurious, so [`try_write`](Self::try_write) may still fail with
+            /// [`io::ErrorKind::WouldBlock`].
+            pub async fn writable(&self) -> io::Result<()> {
+                self.inner.writable().await.map(|_| ())
+            }
+
+            /// Tries to write a packet without waiting.
+            ///
+            /// Fails with [`io::ErrorKind::WouldBlock`], if the queue is not writable.
+            pub fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+                try_write_io(&self.inner, |mut inner| inner.write(buf))
+            }
+
+            /// Like [`try_write`](Self::try_write), but writes from multiple buffers.
+            pub fn try_write_vectored(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+                try_write_io(&self.inner, |mut inner| inner.write_vectored(bufs))
             }
         }
+
+        impl AsyncWrite for $t {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                poll_write_io(&self.inner, cx, |mut inner| inner.write(buf))
+            }
+
+            fn poll_write_vectored(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<io::Result<usize>> {
+                poll_write_io(&self.inner, cx, |mut inner| inner.write_vectored(bufs))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                poll_write_io(&self.inner, cx, |mut inner| inner.flush())
+            }
+
+            fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        impl tokio::io::AsyncWrite for $t {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                poll_write_io(&self.inner, cx, |mut inner| inner.write(buf))
+            }
+
+            fn poll_write_vectored(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<io::Result<usize>> {
+                poll_write_io(&self.inner, cx, |mut inner| inner.write_vectored(bufs))
+            }
+
+            fn is_write_vectored(&self) -> bool {
+                true
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                poll_write_io(&self.inner, cx, |mut inner| inner.flush())
+            }
+
+            fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+    };
+}
+
+impl_read!(TokioFdQueue);
+impl_write!(TokioFdQueue);
+impl_read!(ReadHalf);
+impl_write!(WriteHalf);
+
+impl AsRawFd for TokioFdQueue {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
     }
+}
+
+impl AsFd for TokioFdQueue {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.get_ref().as_fd()
+    }
+}
+
+impl AsRawFd for ReadHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for ReadHalf {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.get_ref().as_fd()
+    }
+}
+
+impl AsRawFd for PacketSender {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for PacketSender {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.get_ref().as_fd()
+    }
+}
+
+impl AsRawFd for WriteHalf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
 
-    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Poll::Ready(Ok(()))
+impl AsFd for WriteHalf {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.get_ref().as_fd()
     }
 }
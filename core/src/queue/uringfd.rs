@@ -0,0 +1,477 @@
+use crate::error::PacketTooLarge;
+use crate::queue::FdQueueT;
+use crate::traits::AsyncQueueT;
+use crate::Error;
+use futures::task::AtomicWaker;
+use futures::{AsyncRead, AsyncWrite};
+use io_uring::{cqueue, opcode, squeue, types, IoUring, Probe};
+use log::warn;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+
+/// Size of a single I/O buffer. Fits a 64 KiB GSO packet together with any TUN/TAP headers.
+const BUFFER_SIZE: usize = 17 * 4096;
+/// Number of buffers provided to the kernel for multishot reads.
+const POOL_SIZE: u16 = 16;
+const RING_ENTRIES: u32 = 64;
+
+const BUFFER_GROUP: u16 = 0;
+const READ_BUFFER_INDEX: u16 = 0;
+const WRITE_BUFFER_INDEX: u16 = 1;
+
+const READ_TOKEN: u64 = 1;
+const WRITE_TOKEN: u64 = 2;
+const PROVIDE_TOKEN: u64 = 3;
+const CANCEL_TOKEN: u64 = 4;
+
+/// Completion notifications, delivered by eventfd registered with the ring.
+struct Events {
+    eventfd: fs::File,
+    closed: AtomicBool,
+    read: AtomicWaker,
+    write: AtomicWaker,
+}
+
+impl Events {
+    fn new() -> io::Result<Arc<Self>> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Arc::new(Self {
+            eventfd: unsafe { fs::File::from_raw_fd(fd) },
+            closed: AtomicBool::new(false),
+            read: AtomicWaker::new(),
+            write: AtomicWaker::new(),
+        }))
+    }
+
+    fn run(&self) {
+        let mut counter = [0u8; 8];
+        while (&self.eventfd).read_exact(&mut counter).is_ok() {
+            if self.closed.load(Ordering::Acquire) {
+                break;
+            }
+            self.read.wake();
+            self.write.wake();
+        }
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        let _ = (&self.eventfd).write(&1u64.to_ne_bytes());
+    }
+}
+
+struct ReadState {
+    /// Multishot reads with kernel-selected buffers from `pool` are used
+    multishot: bool,
+    in_flight: bool,
+    /// Received packets: buffer id (for multishot reads) and result of the read
+    completed: VecDeque<(Option<u16>, io::Result<usize>)>,
+}
+
+struct WriteState {
+    in_flight: bool,
+    len: usize,
+    /// Error of the last completed write, reported by the next write or flush
+    error: Option<io::Error>,
+}
+
+/// Queue, that submits reads and writes on the device through [io_uring](https://kernel.dk/io_uring.pdf).
+///
+/// Reads are multishot, if supported by kernel (Linux 6.7+): a single submission keeps receiving
+/// packets into a pool of kernel-selected buffers. Otherwise, every read is submitted separately
+/// into a registered buffer. Writes are copied into a registered buffer and submitted
+/// immediately, so at most one write is in flight. Result of this write is reported by
+/// the following write or flush.
+///
+/// Completions are awaited by a helper thread, so this queue does not depend on any specific
+/// async runtime.
+pub struct UringFdQueue {
+    device: fs::File,
+    ring: IoUring,
+    events: Arc<Events>,
+    fixed_buffers: bool,
+    read_buffer: Box<[u8]>,
+    write_buffer: Box<[u8]>,
+    pool: Box<[u8]>,
+    read: ReadState,
+    write: WriteState,
+}
+
+impl AsyncQueueT for UringFdQueue {}
+
+impl FdQueueT for UringFdQueue {
+    const BLOCKING: bool = true;
+
+    fn new(device: OwnedFd) -> Result<Self, Error> {
+        Self::with_device(device.into()).map_err(|source| Error::OperationFailed {
+            operation: "io_uring setup",
+            source,
+        })
+    }
+}
+
+impl UringFdQueue {
+    fn with_device(device: fs::File) -> io::Result<Self> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        let events = Events::new()?;
+        ring.submitter()
+            .register_eventfd(events.eventfd.as_raw_fd())?;
+
+        let mut probe = Probe::new();
+        let multishot = ring.submitter().register_probe(&mut probe).is_ok()
+            && probe.is_supported(opcode::ReadMulti::CODE)
+            && probe.is_supported(opcode::ProvideBuffers::CODE);
+
+        let mut queue = Self {
+            device,
+            ring,
+            events,
+            fixed_buffers: false,
+            read_buffer: vec![0u8; BUFFER_SIZE].into_boxed_slice(),
+            write_buffer: vec![0u8; BUFFER_SIZE].into_boxed_slice(),
+            pool: Box::default(),
+            read: ReadState {
+                multishot,
+                in_flight: false,
+                completed: VecDeque::new(),
+            },
+            write: WriteState {
+                in_flight: false,
+                len: 0,
+                error: None,
+            },
+        };
+
+        let iovecs = [
+            libc::iovec {
+                iov_base: queue.read_buffer.as_mut_ptr() as _,
+                iov_len: BUFFER_SIZE,
+            },
+            libc::iovec {
+                iov_base: queue.write_buffer.as_mut_ptr() as _,
+                iov_len: BUFFER_SIZE,
+            },
+        ];
+        // Buffers are owned by the queue and never reallocated, so they outlive the registration.
+        // Registration can fail due to RLIMIT_MEMLOCK, regular reads and writes are used then.
+        queue.fixed_buffers = unsafe { queue.ring.submitter().register_buffers(&iovecs) }.is_ok();
+
+        if multishot {
+            queue.pool = vec![0u8; BUFFER_SIZE * POOL_SIZE as usize].into_boxed_slice();
+            let entry = opcode::ProvideBuffers::new(
+                queue.pool.as_mut_ptr(),
+                BUFFER_SIZE as _,
+                POOL_SIZE,
+                BUFFER_GROUP,
+                0,
+            )
+            .build()
+            .user_data(PROVIDE_TOKEN);
+            queue.push(entry)?;
+            queue.ring.submit()?;
+        }
+
+        let events = queue.events.clone();
+        thread::Builder::new()
+            .name("tunio-uring".into())
+            .spawn(move || events.run())?;
+
+        Ok(queue)
+    }
+
+    fn push(&mut self, entry: squeue::Entry) -> io::Result<()> {
+        // Entries reference buffers owned by the queue, which wait for completion on drop.
+        unsafe {
+            if self.ring.submission().push(&entry).is_err() {
+                self.ring.submit()?;
+                self.ring
+                    .submission()
+                    .push(&entry)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reap(&mut self) {
+        for cqe in self.ring.completion() {
+            let result = cqe.result();
+            let result = if result < 0 {
+                Err(io::Error::from_raw_os_error(-result))
+            } else {
+                Ok(result as usize)
+            };
+
+            match cqe.user_data() {
+                READ_TOKEN => {
+                    if !cqueue::more(cqe.flags()) {
+                        self.read.in_flight = false;
+                    }
+                    match result {
+                        // Pool is exhausted, read is resubmitted after buffers are returned
+                        Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {}
+                        result => self
+                            .read
+                            .completed
+                            .push_back((cqueue::buffer_select(cqe.flags()), result)),
+                    }
+                }
+                WRITE_TOKEN => {
+                    self.write.in_flight = false;
+                    self.write.error = match result {
+                        Ok(n) if n == self.write.len => None,
+                        Ok(_) => Some(io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "packet was not written completely",
+                        )),
+                        Err(e) => Some(e),
+                    };
+                }
+                PROVIDE_TOKEN => {
+                    if let Err(e) = result {
+                        warn!("Failed to provide buffers to io_uring: {e}");
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn submit_read(&mut self) -> io::Result<()> {
+        let fd = types::Fd(self.device.as_raw_fd());
+        let entry = if self.read.multishot {
+            opcode::ReadMulti::new(fd, 0, BUFFER_GROUP).build()
+        } else if self.fixed_buffers {
+            opcode::ReadFixed::new(
+                fd,
+                self.read_buffer.as_mut_ptr(),
+                BUFFER_SIZE as _,
+                READ_BUFFER_INDEX,
+            )
+            .build()
+        } else {
+            opcode::Read::new(fd, self.read_buffer.as_mut_ptr(), BUFFER_SIZE as _).build()
+        };
+
+        self.push(entry.user_data(READ_TOKEN))?;
+        self.ring.submit()?;
+        self.read.in_flight = true;
+        Ok(())
+    }
+
+    fn submit_write(&mut self, len: usize) -> io::Result<()> {
+        let fd = types::Fd(self.device.as_raw_fd());
+        let entry = if self.fixed_buffers {
+            opcode::WriteFixed::new(fd, self.write_buffer.as_ptr(), len as _, WRITE_BUFFER_INDEX)
+                .build()
+        } else {
+            opcode::Write::new(fd, self.write_buffer.as_ptr(), len as _).build()
+        };
+
+        self.push(entry.user_data(WRITE_TOKEN))?;
+        self.ring.submit()?;
+        self.write.in_flight = true;
+        self.write.len = len;
+        Ok(())
+    }
+
+    /// Copies received packet into `bufs` and returns its buffer to the kernel.
+    fn take_packet(
+        &mut self,
+        buffer_id: Option<u16>,
+        result: io::Result<usize>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> io::Result<usize> {
+        let packet_len = result?;
+
+        let copied = {
+            let packet = match buffer_id {
+                Some(bid) => &self.pool[bid as usize * BUFFER_SIZE..][..BUFFER_SIZE],
+                None => &self.read_buffer[..],
+            };
+
+            let capacity = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+            if packet_len > capacity || packet_len > BUFFER_SIZE {
+                Err(PacketTooLarge {
+                    required: Some(packet_len),
+                    capacity: capacity.min(BUFFER_SIZE),
+                })
+            } else {
+                let mut packet = &packet[..packet_len];
+                for buf in bufs.iter_mut() {
+                    let n = buf.len().min(packet.len());
+                    buf[..n].copy_from_slice(&packet[..n]);
+                    packet = &packet[n..];
+                }
+                Ok(packet_len)
+            }
+        };
+
+        if let Some(bid) = buffer_id {
+            let entry = opcode::ProvideBuffers::new(
+                self.pool[bid as usize * BUFFER_SIZE..].as_mut_ptr(),
+                BUFFER_SIZE as _,
+                1,
+                BUFFER_GROUP,
+                bid,
+            )
+            .build()
+            .user_data(PROVIDE_TOKEN);
+            self.push(entry)?;
+            self.ring.submit()?;
+        }
+
+        Ok(copied?)
+    }
+
+    fn poll_read_packet(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.events.read.register(cx.waker());
+        loop {
+            self.reap();
+            if let Some((buffer_id, result)) = self.read.completed.pop_front() {
+                return Poll::Ready(self.take_packet(buffer_id, result, bufs));
+            }
+            if self.read.in_flight {
+                return Poll::Pending;
+            }
+            self.submit_read()?;
+        }
+    }
+
+    fn poll_write_packet(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        if let Poll::Ready(Err(e)) = self.poll_write_completion(cx) {
+            return Poll::Ready(Err(e));
+        }
+        if self.write.in_flight {
+            return Poll::Pending;
+        }
+
+        let len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+        if len > BUFFER_SIZE {
+            return Poll::Ready(Err(PacketTooLarge {
+                required: Some(len),
+                capacity: BUFFER_SIZE,
+            }
+            .into()));
+        }
+
+        let mut offset = 0;
+        for buf in bufs {
+            self.write_buffer[offset..][..buf.len()].copy_from_slice(buf);
+            offset += buf.len();
+        }
+        self.submit_write(len)?;
+
+        Poll::Ready(Ok(len))
+    }
+
+    /// Waits for the submitted write and takes its error.
+    fn poll_write_completion(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.events.write.register(cx.waker());
+        self.reap();
+        if self.write.in_flight {
+            return Poll::Pending;
+        }
+        match self.write.error.take() {
+            Some(e) => Poll::Ready(Err(e)),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl Drop for UringFdQueue {
+    fn drop(&mut self) {
+        if self.read.in_flight {
+            let entry = opcode::AsyncCancel::new(READ_TOKEN)
+                .build()
+                .user_data(CANCEL_TOKEN);
+            let _ = self.push(entry);
+        }
+
+        // Kernel must not access buffers after they are freed
+        while self.read.in_flight || self.write.in_flight {
+            if self.ring.submit_and_wait(1).is_err() {
+                break;
+            }
+            self.reap();
+        }
+
+        self.events.close();
+    }
+}
+
+impl AsRawFd for UringFdQueue {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+impl AsFd for UringFdQueue {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.device.as_fd()
+    }
+}
+
+impl AsyncRead for UringFdQueue {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut()
+            .poll_read_packet(cx, &mut [IoSliceMut::new(buf)])
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_read_packet(cx, bufs)
+    }
+}
+
+impl AsyncWrite for UringFdQueue {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_packet(cx, &[IoSlice::new(buf)])
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write_packet(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_completion(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_completion(cx)
+    }
+}
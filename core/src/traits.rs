@@ -1,10 +1,18 @@
 use crate::config::{IfConfig, IfConfigBuilder};
+use crate::packet::{check_written, with_scratch, Packet, PacketStream, RecvPacket, SendPacket};
 use crate::Error;
 use futures::{AsyncRead, AsyncWrite};
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 
 pub trait PlatformIfConfigT: Default + Clone {
     type Builder: Default;
+
+    /// Checks, that `name` can be used as an interface name on this platform.
+    ///
+    /// Called by [`IfConfigBuilder::build`].
+    fn validate_name(_name: &str) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 pub trait DriverT: Sized {
@@ -32,7 +40,7 @@ pub trait InterfaceT: Sized {
 
     fn up(&mut self) -> Result<(), Error>;
     fn down(&mut self) -> Result<(), Error>;
-    fn handle(&self) -> netconfig::Interface;
+    fn handle(&self) -> Result<netconfig::Interface, Error>;
 
     fn config_builder() -> IfConfigBuilder<Self::PlatformIfConfig> {
         IfConfigBuilder::default()
@@ -41,3 +49,57 @@ pub trait InterfaceT: Sized {
 
 pub trait SyncQueueT: Read + Write {}
 pub trait AsyncQueueT: AsyncRead + AsyncWrite + Unpin {}
+
+/// Packet-oriented access to a queue.
+///
+/// Each read from a queue returns exactly one packet, and each write sends exactly one packet.
+/// This trait makes it explicit and takes care of buffer allocation.
+pub trait PacketQueueT: SyncQueueT {
+    /// Maximum size of a packet, that can be read from this queue.
+    ///
+    /// It is the largest packet, that the device can produce, plus size of headers, added by
+    /// the driver. It does not depend on the current MTU, so it stays valid when MTU is changed.
+    fn max_packet_size(&self) -> io::Result<usize>;
+
+    /// Receives one packet into a newly allocated buffer of the packet size.
+    fn recv_packet(&mut self) -> io::Result<Packet> {
+        with_scratch(self.max_packet_size()?, |buf| {
+            let n = self.read(buf)?;
+            Ok(Packet::copy_from_slice(&buf[..n]))
+        })
+    }
+
+    /// Sends one packet. Fails, if the packet could not be written as a whole.
+    fn send_packet(&mut self, packet: &[u8]) -> io::Result<()> {
+        let n = self.write(packet)?;
+        check_written(n, packet)
+    }
+}
+
+/// Async version of [`PacketQueueT`].
+pub trait AsyncPacketQueueT: AsyncQueueT {
+    /// Maximum size of a packet, that can be read from this queue.
+    ///
+    /// It is the largest packet, that the device can produce, plus size of headers, added by
+    /// the driver. It does not depend on the current MTU, so it stays valid when MTU is changed.
+    fn max_packet_size(&self) -> io::Result<usize>;
+
+    /// Receives one packet into a newly allocated buffer of the packet size.
+    fn recv_packet(&mut self) -> RecvPacket<'_, Self> {
+        RecvPacket::new(self)
+    }
+
+    /// Sends one packet. Fails, if the packet could not be written as a whole.
+    fn send_packet<'a>(&'a mut self, packet: &'a [u8]) -> SendPacket<'a, Self> {
+        SendPacket::new(self, packet)
+    }
+
+    /// Converts the queue into a [`Stream`](futures::Stream) of received packets, that is also
+    /// a [`Sink`](futures::Sink) of packets to send.
+    fn into_stream(self) -> PacketStream<Self>
+    where
+        Self: Sized,
+    {
+        PacketStream::new(self)
+    }
+}
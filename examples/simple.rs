@@ -1,9 +1,9 @@
 use etherparse::PacketBuilder;
-use futures::AsyncReadExt;
 use futures::AsyncWriteExt;
+use futures::StreamExt;
 use std::thread::sleep;
 use std::time::Duration;
-use tunio::traits::{DriverT, InterfaceT};
+use tunio::traits::{AsyncPacketQueueT, DriverT, InterfaceT};
 use tunio::{DefaultAsyncInterface, DefaultDriver};
 
 #[tokio::main]
@@ -20,7 +20,7 @@ async fn main() {
     let interface_config = interface_config.build().unwrap();
 
     let mut interface = DefaultAsyncInterface::new_up(&mut driver, interface_config).unwrap();
-    let iff = interface.handle();
+    let iff = interface.handle().unwrap();
 
     iff.add_address("18.3.5.6/24".parse().unwrap()).unwrap();
     iff.add_address("20.3.5.6/24".parse().unwrap()).unwrap();
@@ -44,11 +44,9 @@ async fn main() {
         sleep(Duration::from_secs(1));
     }
 
-    let mut buf = vec![0u8; 4096];
-    while let Ok(n) = interface.read(buf.as_mut_slice()).await {
-        buf.truncate(n);
-        println!("{buf:x?}");
-        buf.resize(4096, 0u8);
+    let mut packets = interface.into_stream();
+    while let Some(Ok(packet)) = packets.next().await {
+        println!("{packet:x?}");
     }
 
     tokio::signal::ctrl_c().await;
@@ -0,0 +1,699 @@
+//! Privilege separation: interfaces are created by a privileged helper process and
+//! passed to an unprivileged one over a Unix socket (`SCM_RIGHTS`).
+//!
+//! The helper calls [`serve`] for every connected client. The client creates its [`Driver`]
+//! with [`Driver::with_helper`], after that interfaces are created as usual:
+//!
+//! ```no_run
+//! use std::os::unix::net::UnixStream;
+//! use tunio_core::traits::InterfaceT;
+//! use tunio_linux::{Driver, Interface};
+//!
+//! let (client, server) = UnixStream::pair().unwrap();
+//! // `server` is passed to the privileged process, that calls `tunio_linux::helper::serve`
+//! # std::thread::spawn(move || tunio_linux::helper::serve(&server).ok());
+//!
+//! let mut driver = Driver::with_helper(client);
+//! let config = Interface::config_builder().name("tun%d".into()).build().unwrap();
+//! let interface = Interface::new_up(&mut driver, config).unwrap();
+//! ```
+//!
+//! Only creation of interfaces and changing their state (up/down) are delegated to the helper.
+//! Helper never attaches to interfaces, that exist already, and checks every request with
+//! a policy, see [`serve_with_policy`].
+//! Helper always uses the default clone device, [`device_path`](crate::PlatformIfConfig::device_path)
+//! of the client is ignored.
+//! Other operations, like [`open_queue`](crate::LinuxInterface::open_queue), are performed by
+//! the client itself, so they need their own privileges.
+
+use crate::queue::TUN_DEVICE_PATH;
+use crate::{Driver, Interface, Offload, PlatformIfConfig};
+use netconfig::sys::InterfaceExt;
+use nix::sys::socket::{
+    getsockopt, recvmsg, sendmsg, sockopt, ControlMessage, ControlMessageOwned, MsgFlags,
+};
+use std::collections::HashMap;
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use tunio_core::config::{IfConfig, Layer};
+use tunio_core::traits::{DriverT, InterfaceT, PlatformIfConfigT};
+use tunio_core::Error;
+
+const REQUEST_CREATE: u8 = 1;
+const REQUEST_SET_UP: u8 = 2;
+const REQUEST_REMOVE: u8 = 3;
+
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_ERROR: u8 = 1;
+
+/// Error variants, that are passed to the client as is. Others are reduced to errno and message.
+const ERROR_OTHER: u8 = 0;
+const ERROR_PERMISSION_DENIED: u8 = 1;
+const ERROR_NAME_IN_USE: u8 = 2;
+const ERROR_DEVICE_NODE_MISSING: u8 = 3;
+const ERROR_INTERFACE_NOT_FOUND: u8 = 4;
+const ERROR_INVALID_CONFIG_VALUE: u8 = 5;
+const ERROR_LAYER_MISMATCH: u8 = 6;
+const ERROR_NAME_INVALID: u8 = 7;
+const ERROR_NAME_TOO_LONG: u8 = 8;
+
+/// Capabilities, that can be reported in [`Error::PermissionDenied`]
+const CAPABILITIES: [&str; 2] = ["CAP_NET_ADMIN", "CAP_MKNOD"];
+
+/// Upper bound of a message size, so a broken peer can not make us allocate arbitrary memory
+const MAX_MESSAGE_LEN: usize = 4096;
+
+/// Credentials of a connected client (`SO_PEERCRED`)
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Peer {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Serves requests of a single client with [`default_policy`], until it disconnects.
+pub fn serve(stream: &UnixStream) -> Result<(), Error> {
+    serve_with_policy(stream, default_policy)
+}
+
+/// Serves requests of a single client, until it disconnects.
+///
+/// `policy` is called for every interface, requested by the client. It can reject the request
+/// or adjust its parameters, e.g. force the owner of the interface.
+///
+/// Client can change state only of interfaces, that were created over this connection.
+/// They are tracked by index, so an interface, that was removed and then created again
+/// by someone else under the same name, is not affected.
+/// Errors are reported to the client. This function fails only if the connection is broken.
+pub fn serve_with_policy<F>(stream: &UnixStream, mut policy: F) -> Result<(), Error>
+where
+    F: FnMut(&Peer, &mut IfConfig<PlatformIfConfig>) -> Result<(), Error>,
+{
+    let credentials = getsockopt(stream.as_raw_fd(), sockopt::PeerCredentials)
+        .map_err(|e| Error::from(io::Error::from(e)))?;
+    let peer = Peer {
+        pid: credentials.pid(),
+        uid: credentials.uid(),
+        gid: credentials.gid(),
+    };
+    // Name, that the client knows, to index of the interface
+    let mut created = HashMap::new();
+
+    loop {
+        let request = match recv_message(stream) {
+            Ok((request, _)) => request,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut decoder = Decoder(&request);
+        match decoder.get_u8()? {
+            REQUEST_CREATE => match decode_config(&mut decoder).and_then(|mut params| {
+                policy(&peer, &mut params)?;
+                create_interface(params)
+            }) {
+                Ok((name, index, device)) => {
+                    created.insert(name, index);
+                    send_message(stream, &[RESPONSE_OK], Some(device.as_raw_fd()))?;
+                }
+                Err(e) => send_error(stream, e)?,
+            },
+            REQUEST_SET_UP => {
+                let name = decoder.get_str()?;
+                let up = decoder.get_bool()?;
+                let result = match created.get(&name) {
+                    Some(&index) => match netconfig::Interface::try_from_index(index) {
+                        Ok(handle) => handle.set_up(up).map_err(Error::from),
+                        Err(netconfig::Error::InterfaceNotFound) => {
+                            created.remove(&name);
+                            Err(Error::InterfaceNotFound(name))
+                        }
+                        Err(e) => Err(e.into()),
+                    },
+                    None => Err(Error::InterfaceNotFound(name)),
+                };
+                match result {
+                    Ok(()) => send_message(stream, &[RESPONSE_OK], None)?,
+                    Err(e) => send_error(stream, e)?,
+                }
+            }
+            REQUEST_REMOVE => {
+                let name = decoder.get_str()?;
+                match created.remove(&name) {
+                    Some(_) => send_message(stream, &[RESPONSE_OK], None)?,
+                    None => send_error(stream, Error::InterfaceNotFound(name))?,
+                }
+            }
+            tag => return Err(invalid_data(format!("unknown request: {tag}")).into()),
+        }
+    }
+}
+
+/// Policy of [`serve`]: persistent interfaces are not allowed, owner and group of an interface
+/// can only be set to the client itself.
+pub fn default_policy(peer: &Peer, params: &mut IfConfig<PlatformIfConfig>) -> Result<(), Error> {
+    let platform = &params.platform;
+    let denied = |operation: String| Error::PermissionDenied {
+        operation,
+        capability: None,
+    };
+
+    if platform.persist {
+        return Err(denied("create persistent interface".to_string()));
+    }
+    match platform.owner {
+        Some(owner) if owner != peer.uid => {
+            return Err(denied(format!("set owner of the interface to {owner}")))
+        }
+        _ => {}
+    }
+    match platform.group {
+        Some(group) if group != peer.gid => {
+            return Err(denied(format!("set group of the interface to {group}")))
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn create_interface(params: IfConfig<PlatformIfConfig>) -> Result<(String, u32, OwnedFd), Error> {
+    // Config is not built with the builder, so the name is not validated yet
+    PlatformIfConfig::validate_name(&params.name)?;
+    // Creation attaches to an existing interface of the same name, that would let the client
+    // take over an interface, that it did not create (e.g. change its owner)
+    let name = &params.name;
+    if !name.is_empty() && !name.contains('%') && Path::new("/sys/class/net").join(name).exists() {
+        return Err(Error::NameInUse(name.to_string()));
+    }
+
+    let interface = Interface::new(&mut Driver::new()?, params)?;
+    let name = interface.name().to_string();
+    let index = netconfig::Interface::try_from_name(&name)?.index()?;
+    // Interface stays alive, while the client holds the descriptor
+    let device = unsafe { OwnedFd::from_raw_fd(interface.into_raw_fd()) };
+    Ok((name, index, device))
+}
+
+fn send_error(stream: &UnixStream, error: Error) -> io::Result<()> {
+    let mut response = vec![RESPONSE_ERROR];
+    encode_error(&mut response, &error);
+    send_message(stream, &response, None)
+}
+
+/// Client side of the helper connection, shared by the driver and all interfaces, created by it.
+pub(crate) struct HelperClient(Mutex<UnixStream>);
+
+impl HelperClient {
+    pub(crate) fn new(stream: UnixStream) -> Self {
+        Self(Mutex::new(stream))
+    }
+
+    /// Asks the helper to create an interface and returns its device
+    pub(crate) fn create(&self, params: &IfConfig<PlatformIfConfig>) -> Result<OwnedFd, Error> {
+        let mut request = vec![REQUEST_CREATE];
+        encode_config(&mut request, params);
+
+        match self.request(&request)? {
+            Some(device) => Ok(device),
+            None => Err(invalid_data("helper did not pass a device".to_string()).into()),
+        }
+    }
+
+    /// Asks the helper to bring the interface up or down
+    pub(crate) fn set_up(&self, name: &str, up: bool) -> Result<(), Error> {
+        let mut request = vec![REQUEST_SET_UP];
+        put_str(&mut request, name);
+        request.push(up as u8);
+
+        self.request(&request)?;
+        Ok(())
+    }
+
+    /// Tells the helper, that the interface is removed, so the client can no longer change it
+    pub(crate) fn remove(&self, name: &str) -> Result<(), Error> {
+        let mut request = vec![REQUEST_REMOVE];
+        put_str(&mut request, name);
+
+        self.request(&request)?;
+        Ok(())
+    }
+
+    fn request(&self, request: &[u8]) -> Result<Option<OwnedFd>, Error> {
+        // Requests are not interleaved, so each response matches its request. The lock guards
+        // no invariant of its own, so a request that panicked must not break the others.
+        let stream = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        send_message(&stream, request, None)?;
+        let (response, device) = recv_message(&stream)?;
+
+        let mut decoder = Decoder(&response);
+        match decoder.get_u8()? {
+            RESPONSE_OK => Ok(device),
+            RESPONSE_ERROR => Err(decode_error(&mut decoder)?),
+            tag => Err(invalid_data(format!("unknown response: {tag}")).into()),
+        }
+    }
+}
+
+/// Sends length-prefixed message, optionally attaching a descriptor to it
+fn send_message(stream: &UnixStream, message: &[u8], fd: Option<RawFd>) -> io::Result<()> {
+    let mut buf = (message.len() as u32).to_le_bytes().to_vec();
+    buf.extend_from_slice(message);
+
+    let fds: Vec<RawFd> = fd.into_iter().collect();
+    let cmsgs = match fds.is_empty() {
+        true => vec![],
+        false => vec![ControlMessage::ScmRights(&fds)],
+    };
+
+    // Descriptor is attached to the first chunk, so the rest can be written separately
+    let sent = sendmsg::<()>(
+        stream.as_raw_fd(),
+        &[IoSlice::new(&buf)],
+        &cmsgs,
+        MsgFlags::empty(),
+        None,
+    )
+    .map_err(io::Error::from)?;
+    (&*stream).write_all(&buf[sent..])
+}
+
+/// Receives length-prefixed message and a descriptor, that is attached to it
+fn recv_message(stream: &UnixStream) -> io::Result<(Vec<u8>, Option<OwnedFd>)> {
+    let mut len = [0u8; 4];
+    let mut cmsg_buffer = nix::cmsg_space!([RawFd; 1]);
+
+    let msg = recvmsg::<()>(
+        stream.as_raw_fd(),
+        &mut [IoSliceMut::new(&mut len)],
+        Some(&mut cmsg_buffer),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )
+    .map_err(io::Error::from)?;
+    let received = msg.bytes;
+
+    let mut device = None;
+    for cmsg in msg.cmsgs() {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg {
+            for fd in fds {
+                let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+                // Unexpected descriptors are closed
+                device.get_or_insert(fd);
+            }
+        }
+    }
+
+    if received == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    (&*stream).read_exact(&mut len[received..])?;
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(invalid_data(format!("message is too large: {len}")));
+    }
+    let mut message = vec![0u8; len];
+    (&*stream).read_exact(&mut message)?;
+
+    Ok((message, device))
+}
+
+fn encode_error(buf: &mut Vec<u8>, error: &Error) {
+    match error {
+        Error::PermissionDenied {
+            operation,
+            capability,
+        } => {
+            buf.push(ERROR_PERMISSION_DENIED);
+            put_str(buf, operation);
+            put_str(buf, capability.unwrap_or_default());
+        }
+        Error::NameInUse(name) => {
+            buf.push(ERROR_NAME_IN_USE);
+            put_str(buf, name);
+        }
+        Error::DeviceNodeMissing { path } => {
+            buf.push(ERROR_DEVICE_NODE_MISSING);
+            put_str(buf, &path.to_string_lossy());
+        }
+        Error::InterfaceNotFound(name) => {
+            buf.push(ERROR_INTERFACE_NOT_FOUND);
+            put_str(buf, name);
+        }
+        Error::InvalidConfigValue {
+            name,
+            value,
+            reason,
+        } => {
+            buf.push(ERROR_INVALID_CONFIG_VALUE);
+            put_str(buf, name);
+            put_str(buf, value);
+            put_str(buf, reason);
+        }
+        Error::LayerMismatch { expected, actual } => {
+            buf.push(ERROR_LAYER_MISMATCH);
+            put_layer(buf, *expected);
+            put_layer(buf, *actual);
+        }
+        Error::InterfaceNameInvalid => buf.push(ERROR_NAME_INVALID),
+        Error::InterfaceNameTooLong(len, max) => {
+            buf.push(ERROR_NAME_TOO_LONG);
+            put_u32(buf, *len as u32);
+            put_u32(buf, *max as u32);
+        }
+        error => {
+            let errno = match error {
+                Error::Io(e)
+                | Error::NetConfigError(netconfig::Error::Io(e))
+                | Error::OperationFailed { source: e, .. } => e.raw_os_error(),
+                _ => None,
+            };
+            buf.push(ERROR_OTHER);
+            put_u32(buf, errno.unwrap_or(0) as u32);
+            put_str(buf, &error.to_string());
+        }
+    }
+}
+
+fn decode_error(decoder: &mut Decoder<'_>) -> io::Result<Error> {
+    Ok(match decoder.get_u8()? {
+        ERROR_PERMISSION_DENIED => {
+            let operation = decoder.get_str()?;
+            let capability = decoder.get_str()?;
+            Error::PermissionDenied {
+                operation,
+                capability: CAPABILITIES.into_iter().find(|c| *c == capability),
+            }
+        }
+        ERROR_NAME_IN_USE => Error::NameInUse(decoder.get_str()?),
+        ERROR_DEVICE_NODE_MISSING => Error::DeviceNodeMissing {
+            path: decoder.get_str()?.into(),
+        },
+        ERROR_INTERFACE_NOT_FOUND => Error::InterfaceNotFound(decoder.get_str()?),
+        ERROR_INVALID_CONFIG_VALUE => Error::InvalidConfigValue {
+            name: decoder.get_str()?,
+            value: decoder.get_str()?,
+            reason: decoder.get_str()?,
+        },
+        ERROR_LAYER_MISMATCH => Error::LayerMismatch {
+            expected: decoder.get_layer()?,
+            actual: decoder.get_layer()?,
+        },
+        ERROR_NAME_INVALID => Error::InterfaceNameInvalid,
+        ERROR_NAME_TOO_LONG => {
+            Error::InterfaceNameTooLong(decoder.get_u32()? as usize, decoder.get_u32()? as usize)
+        }
+        ERROR_OTHER => {
+            let errno = decoder.get_u32()? as i32;
+            let message = decoder.get_str()?;
+            Error::Io(match errno {
+                0 => io::Error::new(io::ErrorKind::Other, message),
+                errno => io::Error::from_raw_os_error(errno),
+            })
+        }
+        kind => return Err(invalid_data(format!("unknown error: {kind}"))),
+    })
+}
+
+fn encode_config(buf: &mut Vec<u8>, params: &IfConfig<PlatformIfConfig>) {
+    put_str(buf, &params.name);
+    put_layer(buf, params.layer);
+
+    // Every field is listed, so a new one is not forgotten here
+    let PlatformIfConfig {
+        multi_queue,
+        persist,
+        owner,
+        group,
+        packet_info,
+        vnet_hdr,
+        offload,
+        // Helper uses its own clone device, see `decode_config`
+        device_path: _,
+        create_device_node: _,
+    } = &params.platform;
+    buf.push(*multi_queue as u8);
+    buf.push(*persist as u8);
+    put_option(buf, *owner);
+    put_option(buf, *group);
+    buf.push(*packet_info as u8);
+    buf.push(*vnet_hdr as u8);
+    put_u32(buf, offload.bits());
+}
+
+fn decode_config(decoder: &mut Decoder<'_>) -> Result<IfConfig<PlatformIfConfig>, Error> {
+    let name = decoder.get_str()?;
+    let layer = decoder.get_layer()?;
+
+    let platform = PlatformIfConfig {
+        multi_queue: decoder.get_bool()?,
+        persist: decoder.get_bool()?,
+        owner: decoder.get_option()?,
+        group: decoder.get_option()?,
+        packet_info: decoder.get_bool()?,
+        vnet_hdr: decoder.get_bool()?,
+        offload: Offload::from_bits(decoder.get_u32()?),
+        // Client must not make the helper open or create arbitrary files
+        device_path: TUN_DEVICE_PATH.into(),
+        create_device_node: false,
+    };
+
+    Ok(IfConfig {
+        name,
+        layer,
+        platform,
+    })
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    put_u32(buf, value.len() as u32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn put_layer(buf: &mut Vec<u8>, layer: Layer) {
+    buf.push(match layer {
+        Layer::L2 => 2,
+        Layer::L3 => 3,
+    });
+}
+
+fn put_option(buf: &mut Vec<u8>, value: Option<u32>) {
+    match value {
+        Some(value) => {
+            buf.push(1);
+            put_u32(buf, value);
+        }
+        None => buf.push(0),
+    }
+}
+
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid_data("message is truncated".to_string()));
+        }
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(value)
+    }
+
+    fn get_u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn get_bool(&mut self) -> io::Result<bool> {
+        Ok(self.get_u8()? != 0)
+    }
+
+    fn get_u32(&mut self) -> io::Result<u32> {
+        let mut value = [0u8; 4];
+        value.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(value))
+    }
+
+    fn get_str(&mut self) -> io::Result<String> {
+        let len = self.get_u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| invalid_data(e.to_string()))
+    }
+
+    fn get_layer(&mut self) -> io::Result<Layer> {
+        match self.get_u8()? {
+            2 => Ok(Layer::L2),
+            3 => Ok(Layer::L3),
+            layer => Err(invalid_data(format!("unknown layer: {layer}"))),
+        }
+    }
+
+    fn get_option(&mut self) -> io::Result<Option<u32>> {
+        match self.get_bool()? {
+            true => Ok(Some(self.get_u32()?)),
+            false => Ok(None),
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::discriminant;
+    use std::path::PathBuf;
+
+    fn roundtrip_error(error: &Error) -> Error {
+        let mut buf = Vec::new();
+        encode_error(&mut buf, error);
+        let mut decoder = Decoder(&buf);
+        let decoded = decode_error(&mut decoder).unwrap();
+        assert!(decoder.0.is_empty());
+        decoded
+    }
+
+    fn config() -> IfConfig<PlatformIfConfig> {
+        IfConfig {
+            name: "tap%d".to_string(),
+            layer: Layer::L2,
+            platform: PlatformIfConfig {
+                multi_queue: true,
+                persist: true,
+                owner: Some(1000),
+                group: Some(100),
+                packet_info: true,
+                vnet_hdr: true,
+                offload: Offload::CSUM | Offload::TSO4,
+                device_path: PathBuf::from("/tmp/tun"),
+                create_device_node: true,
+            },
+        }
+    }
+
+    #[test]
+    fn config_roundtrip() {
+        let config = config();
+        let mut buf = Vec::new();
+        encode_config(&mut buf, &config);
+        let mut decoder = Decoder(&buf);
+        let decoded = decode_config(&mut decoder).unwrap();
+        assert!(decoder.0.is_empty());
+
+        assert_eq!(decoded.name, config.name);
+        assert_eq!(decoded.layer, config.layer);
+        let PlatformIfConfig {
+            multi_queue,
+            persist,
+            owner,
+            group,
+            packet_info,
+            vnet_hdr,
+            offload,
+            device_path,
+            create_device_node,
+        } = decoded.platform;
+        assert_eq!(multi_queue, config.platform.multi_queue);
+        assert_eq!(persist, config.platform.persist);
+        assert_eq!(owner, config.platform.owner);
+        assert_eq!(group, config.platform.group);
+        assert_eq!(packet_info, config.platform.packet_info);
+        assert_eq!(vnet_hdr, config.platform.vnet_hdr);
+        assert_eq!(offload, config.platform.offload);
+        // Not passed to the helper
+        assert_eq!(device_path, PathBuf::from(TUN_DEVICE_PATH));
+        assert!(!create_device_node);
+    }
+
+    #[test]
+    fn truncated_config() {
+        let mut buf = Vec::new();
+        encode_config(&mut buf, &config());
+        buf.pop();
+        assert!(decode_config(&mut Decoder(&buf)).is_err());
+    }
+
+    #[test]
+    fn passed_errors_roundtrip() {
+        let errors = [
+            Error::PermissionDenied {
+                operation: "TUNSETIFF".to_string(),
+                capability: Some("CAP_NET_ADMIN"),
+            },
+            Error::PermissionDenied {
+                operation: "mknod".to_string(),
+                capability: None,
+            },
+            Error::NameInUse("tun0".to_string()),
+            Error::DeviceNodeMissing {
+                path: "/dev/net/tun".into(),
+            },
+            Error::InterfaceNotFound("tun0".to_string()),
+            Error::InvalidConfigValue {
+                name: "owner".to_string(),
+                value: "0".to_string(),
+                reason: "denied by policy".to_string(),
+            },
+            Error::LayerMismatch {
+                expected: Layer::L2,
+                actual: Layer::L3,
+            },
+            Error::InterfaceNameInvalid,
+            Error::InterfaceNameTooLong(16, 15),
+        ];
+
+        for error in &errors {
+            let decoded = roundtrip_error(error);
+            assert_eq!(discriminant(&decoded), discriminant(error), "{error}");
+            assert_eq!(decoded.to_string(), error.to_string());
+        }
+    }
+
+    #[test]
+    fn other_errors_roundtrip() {
+        let with_errno = [
+            Error::Io(io::Error::from_raw_os_error(libc::EBUSY)),
+            Error::NetConfigError(netconfig::Error::Io(io::Error::from_raw_os_error(
+                libc::ENODEV,
+            ))),
+            Error::OperationFailed {
+                operation: "TUNSETPERSIST",
+                source: io::Error::from_raw_os_error(libc::EINVAL),
+            },
+        ];
+        for error in &with_errno {
+            let expected = match error {
+                Error::Io(e)
+                | Error::NetConfigError(netconfig::Error::Io(e))
+                | Error::OperationFailed { source: e, .. } => e.raw_os_error(),
+                _ => unreachable!(),
+            };
+            match roundtrip_error(error) {
+                Error::Io(e) => assert_eq!(e.raw_os_error(), expected, "{error}"),
+                decoded => panic!("{error}: unexpected {decoded:?}"),
+            }
+        }
+
+        let without_errno = [
+            Error::Io(io::Error::new(io::ErrorKind::Other, "custom")),
+            Error::InterfaceNameUnicodeError,
+            Error::LibraryNotLoaded {
+                reason: "missing".to_string(),
+            },
+            Error::InterfaceNameError("bad".to_string()),
+            Error::LayerUnsupported(Layer::L2),
+        ];
+        for error in &without_errno {
+            match roundtrip_error(error) {
+                Error::Io(e) => assert_eq!(e.to_string(), error.to_string()),
+                decoded => panic!("{error}: unexpected {decoded:?}"),
+            }
+        }
+    }
+}
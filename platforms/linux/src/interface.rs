@@ -1,43 +1,179 @@
-use super::queue::{create_device, Device};
+use super::helper::HelperClient;
+use super::queue::{
+    check_existing_device, create_device, device_flags, device_info, set_blocking, set_group,
+    set_offload, set_owner, set_persist, CloneDevice, Device,
+};
 use super::Driver;
-use super::PlatformIfConfig;
+use super::{Offload, PacketInfo, PlatformIfConfig, VirtioNetHdr};
 use delegate::delegate;
 use futures::{AsyncRead, AsyncWrite};
+use libc::{IFF_MULTI_QUEUE, IFF_NO_PI, IFF_TAP, IFF_VNET_HDR};
 use log::debug;
 use netconfig::sys::InterfaceExt;
 use std::io;
-use std::io::{Read, Write};
+use std::io::{IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use tunio_core::config::IfConfig;
+#[cfg(feature = "async-io")]
+use tunio_core::queue::asynciofd::AsyncIoFdQueue;
+#[cfg(feature = "mio")]
+use tunio_core::queue::miofd::MioFdQueue;
 use tunio_core::queue::syncfd::SyncFdQueue;
 #[cfg(feature = "tokio")]
-use tunio_core::queue::tokiofd::TokioFdQueue;
+use tunio_core::queue::tokiofd::{PacketSender, ReadHalf, TokioFdQueue, WriteHalf};
+#[cfg(feature = "io-uring")]
+use tunio_core::queue::uringfd::UringFdQueue;
 use tunio_core::queue::FdQueueT;
-use tunio_core::traits::{AsyncQueueT, InterfaceT, SyncQueueT};
+#[cfg(any(feature = "tokio", feature = "async-io", feature = "io-uring"))]
+use tunio_core::traits::AsyncPacketQueueT;
+use tunio_core::traits::{AsyncQueueT, InterfaceT, PacketQueueT, SyncQueueT};
 use tunio_core::Error;
 
 pub struct LinuxInterface<Q> {
     name: String,
+    pub(crate) flags: libc::c_int,
     pub(crate) queue: Q,
+    /// Clone device, that is used to attach more queues
+    clone_device: CloneDevice,
+    /// Helper, that created this interface, if any
+    helper: Option<Arc<HelperClient>>,
 }
 
+/// Ethernet header with a VLAN tag
+const ETHERNET_HEADER_LEN: usize = 18;
+/// Largest MTU of a TUN/TAP interface and largest size of GSO super-packet
+const MAX_PAYLOAD_LEN: usize = u16::MAX as usize;
+
 impl<Q> LinuxInterface<Q> {
+    /// Name of the interface. If it was created from a template, like `tun%d`, the name is resolved.
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Size of the largest packet, that the device can produce.
+    ///
+    /// It is not derived from the current MTU, so buffers stay large enough if MTU is raised later.
+    pub(crate) fn packet_size(&self) -> usize {
+        let vnet_len = match self.flags & IFF_VNET_HDR {
+            0 => 0,
+            _ => VirtioNetHdr::SIZE,
+        };
+        let link_len = match self.flags & IFF_TAP {
+            0 => 0,
+            _ => ETHERNET_HEADER_LEN,
+        };
+        let pi_len = match self.flags & IFF_NO_PI {
+            0 => PacketInfo::SIZE,
+            _ => 0,
+        };
+
+        pi_len + vnet_len + link_len + MAX_PAYLOAD_LEN
+    }
 }
 
-impl<Q: FdQueueT> InterfaceT for LinuxInterface<Q> {
-    type PlatformDriver = Driver;
-    type PlatformIfConfig = PlatformIfConfig;
+impl<Q: FdQueueT> LinuxInterface<Q> {
+    /// Attaches one more queue to this interface.
+    ///
+    /// Interface must be created with [`multi_queue`](crate::PlatformIfConfig::multi_queue) enabled.
+    /// Every returned queue has its own file descriptor, so it can be moved to a separate thread or task.
+    /// The kernel distributes flows between all attached queues, including the one owned by this interface.
+    pub fn open_queue(&self) -> Result<Q, Error> {
+        if self.flags & IFF_MULTI_QUEUE == 0 {
+            return Err(Error::InvalidConfigValue {
+                name: "multi_queue".to_string(),
+                value: false.to_string(),
+                reason: "queues can be added only to multi-queue interfaces".to_string(),
+            });
+        }
 
-    fn new(
-        _driver: &mut Self::PlatformDriver,
-        params: IfConfig<Self::PlatformIfConfig>,
+        let Device { device, .. } =
+            create_device(&self.clone_device, &self.name, self.flags, Q::BLOCKING)?;
+        Q::new(device.into())
+    }
+
+    /// Changes offload features (`TUNSETOFFLOAD`), enabled for this interface.
+    ///
+    /// Fails with `EINVAL` if the kernel does not support some of the requested offloads,
+    /// so callers can retry with a smaller set.
+    pub fn set_offload(&self, offload: Offload) -> Result<(), Error> {
+        if self.flags & IFF_VNET_HDR == 0 {
+            return Err(Error::InvalidConfigValue {
+                name: "offload".to_string(),
+                value: format!("{:#x}", offload.bits()),
+                reason: "requires vnet_hdr".to_string(),
+            });
+        }
+        set_offload(self.queue.as_raw_fd(), offload)
+    }
+
+    /// Attaches to an existing interface instead of creating a new one.
+    ///
+    /// Fails with [`Error::InterfaceNotFound`] if there is no interface with given name and
+    /// with [`Error::LayerMismatch`] if it is a TUN interface, when TAP was requested, or vice versa.
+    /// Name templates (like `tun%d`) and empty names are not allowed here.
+    pub fn open_existing(
+        _driver: &mut Driver,
+        params: IfConfig<PlatformIfConfig>,
     ) -> Result<Self, Error> {
-        let Device { device, name } = create_device(&params.name, params.layer, Q::BLOCKING)?;
-        let queue = Q::new(device.into());
+        let flags = device_flags(params.layer, &params.platform);
+        check_existing_device(&params.name, flags)?;
+        Self::open_device(params, flags)
+    }
+
+    /// Adopts an already open TUN/TAP device, e.g. passed by a privileged process or
+    /// a container runtime.
+    ///
+    /// Name and flags of the interface are queried from the device (`TUNGETIFF`), so the device
+    /// must be already attached to an interface. The descriptor is switched to blocking or
+    /// non-blocking mode, as required by the queue.
+    pub fn from_fd(device: OwnedFd) -> Result<Self, Error> {
+        let (name, flags) = device_info(device.as_raw_fd())?;
+        set_blocking(device.as_raw_fd(), Q::BLOCKING)?;
+
+        Ok(Self {
+            name,
+            flags,
+            queue: Q::new(device)?,
+            clone_device: CloneDevice::default(),
+            helper: None,
+        })
+    }
+
+    fn open_device(params: IfConfig<PlatformIfConfig>, flags: libc::c_int) -> Result<Self, Error> {
+        if params.platform.packet_info && params.platform.vnet_hdr {
+            return Err(Error::InvalidConfigValue {
+                name: "vnet_hdr".to_string(),
+                value: true.to_string(),
+                reason: "cannot be combined with packet_info".to_string(),
+            });
+        }
+        if !params.platform.offload.is_empty() && !params.platform.vnet_hdr {
+            return Err(Error::InvalidConfigValue {
+                name: "offload".to_string(),
+                value: format!("{:#x}", params.platform.offload.bits()),
+                reason: "requires vnet_hdr".to_string(),
+            });
+        }
+
+        let clone_device = CloneDevice::new(&params.platform);
+        let Device { device, name } =
+            create_device(&clone_device, &params.name, flags, Q::BLOCKING)?;
+        if let Some(owner) = params.platform.owner {
+            set_owner(device.as_raw_fd(), owner)?;
+        }
+        if let Some(group) = params.platform.group {
+            set_group(device.as_raw_fd(), group)?;
+        }
+        if params.platform.persist {
+            set_persist(device.as_raw_fd(), true)?;
+        }
+        if !params.platform.offload.is_empty() {
+            set_offload(device.as_raw_fd(), params.platform.offload)?;
+        }
+        let queue = Q::new(device.into())?;
 
         if params.name != name {
             debug!(
@@ -46,29 +182,99 @@ impl<Q: FdQueueT> InterfaceT for LinuxInterface<Q> {
             );
         }
 
-        Ok(Self { name, queue })
+        Ok(Self {
+            name,
+            flags,
+            queue,
+            clone_device,
+            helper: None,
+        })
+    }
+
+    /// Changes persistence of this interface (`TUNSETPERSIST`).
+    ///
+    /// Persistent interface stays in the system after all its queues are closed.
+    pub fn set_persist(&self, persist: bool) -> Result<(), Error> {
+        set_persist(self.queue.as_raw_fd(), persist)
+    }
+
+    /// Removes persistent interface from the system.
+    ///
+    /// To remove an interface, that was made persistent by another process, attach to it using
+    /// [`open_existing`](Self::open_existing), then call this method.
+    pub fn remove(self) -> Result<(), Error> {
+        self.set_persist(false)?;
+        match &self.helper {
+            Some(helper) => helper.remove(&self.name),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<Q: FdQueueT> InterfaceT for LinuxInterface<Q> {
+    type PlatformDriver = Driver;
+    type PlatformIfConfig = PlatformIfConfig;
+
+    fn new(
+        driver: &mut Self::PlatformDriver,
+        params: IfConfig<Self::PlatformIfConfig>,
+    ) -> Result<Self, Error> {
+        if let Some(helper) = &driver.helper {
+            let mut interface = Self::from_fd(helper.create(&params)?)?;
+            interface.helper = Some(helper.clone());
+            return Ok(interface);
+        }
+
+        let flags = device_flags(params.layer, &params.platform);
+        Self::open_device(params, flags)
     }
 
     fn up(&mut self) -> Result<(), Error> {
-        Ok(self.handle().set_up(true)?)
+        match &self.helper {
+            Some(helper) => helper.set_up(&self.name, true),
+            None => Ok(self.handle()?.set_up(true)?),
+        }
     }
 
     fn down(&mut self) -> Result<(), Error> {
-        Ok(self.handle().set_up(false)?)
+        match &self.helper {
+            Some(helper) => helper.set_up(&self.name, false),
+            None => Ok(self.handle()?.set_up(false)?),
+        }
     }
 
-    fn handle(&self) -> netconfig::Interface {
-        netconfig::Interface::try_from_name(self.name()).unwrap()
+    fn handle(&self) -> Result<netconfig::Interface, Error> {
+        netconfig::Interface::try_from_name(self.name()).map_err(|e| match e {
+            netconfig::Error::InterfaceNotFound => Error::InterfaceNotFound(self.name.clone()),
+            e => e.into(),
+        })
     }
 }
 
 pub type Interface = LinuxInterface<SyncFdQueue>;
+
+impl Interface {
+    delegate! {
+        to self.queue {
+            /// See [`SyncFdQueue::recv_batch`]
+            pub fn recv_batch<B: AsMut<[u8]>>(&mut self, bufs: &mut [B], sizes: &mut [usize]) -> io::Result<usize>;
+            /// See [`SyncFdQueue::send_batch`]
+            pub fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize>;
+        }
+    }
+}
 impl SyncQueueT for Interface {}
+impl PacketQueueT for Interface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
 
 impl<Q: SyncQueueT> Read for LinuxInterface<Q> {
     delegate! {
         to self.queue {
             fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error>;
+            fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize>;
         }
     }
 }
@@ -77,20 +283,152 @@ impl<Q: SyncQueueT> Write for LinuxInterface<Q> {
     delegate! {
         to self.queue {
             fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+            fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize>;
             fn flush(&mut self) -> io::Result<()>;
         }
     }
 }
 
+#[cfg(feature = "mio")]
+pub type MioInterface = LinuxInterface<MioFdQueue>;
+
+#[cfg(feature = "mio")]
+impl MioInterface {
+    delegate! {
+        to self.queue {
+            /// See [`MioFdQueue::recv_batch`]
+            pub fn recv_batch<B: AsMut<[u8]>>(&mut self, bufs: &mut [B], sizes: &mut [usize]) -> io::Result<usize>;
+            /// See [`MioFdQueue::send_batch`]
+            pub fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize>;
+        }
+    }
+}
+#[cfg(feature = "mio")]
+impl SyncQueueT for MioInterface {}
+#[cfg(feature = "mio")]
+impl PacketQueueT for MioInterface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<Q: mio::event::Source> mio::event::Source for LinuxInterface<Q> {
+    delegate! {
+        to self.queue {
+            fn register(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()>;
+            fn reregister(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()>;
+            fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()>;
+        }
+    }
+}
+
 #[cfg(feature = "tokio")]
 pub type TokioInterface = LinuxInterface<TokioFdQueue>;
 #[cfg(feature = "tokio")]
 impl AsyncQueueT for TokioInterface {}
 
+#[cfg(feature = "tokio")]
+impl TokioInterface {
+    /// Splits the interface into reading and writing halves, that can be used from different
+    /// tasks.
+    ///
+    /// Interface is removed when both halves are dropped, unless it is persistent.
+    pub fn into_split(self) -> (ReadHalf, WriteHalf) {
+        self.queue.into_split()
+    }
+
+    /// See [`TokioFdQueue::sender`]
+    pub fn sender(&self) -> PacketSender {
+        self.queue.sender()
+    }
+
+    /// See [`TokioFdQueue::readable`]
+    pub async fn readable(&self) -> io::Result<()> {
+        self.queue.readable().await
+    }
+
+    /// See [`TokioFdQueue::writable`]
+    pub async fn writable(&self) -> io::Result<()> {
+        self.queue.writable().await
+    }
+
+    /// See [`TokioFdQueue::try_read`]
+    pub fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.queue.try_read(buf)
+    }
+
+    /// See [`TokioFdQueue::try_write`]
+    pub fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.queue.try_write(buf)
+    }
+
+    /// See [`TokioFdQueue::recv_batch`]
+    pub async fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        self.queue.recv_batch(bufs, sizes).await
+    }
+
+    /// See [`TokioFdQueue::send_batch`]
+    pub async fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        self.queue.send_batch(packets).await
+    }
+}
+#[cfg(feature = "tokio")]
+impl AsyncPacketQueueT for TokioInterface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
+
+#[cfg(feature = "async-io")]
+pub type AsyncIoInterface = LinuxInterface<AsyncIoFdQueue>;
+#[cfg(feature = "async-io")]
+impl AsyncQueueT for AsyncIoInterface {}
+
+#[cfg(feature = "async-io")]
+impl AsyncIoInterface {
+    /// See [`AsyncIoFdQueue::recv_batch`]
+    pub async fn recv_batch<B: AsMut<[u8]>>(
+        &mut self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> io::Result<usize> {
+        self.queue.recv_batch(bufs, sizes).await
+    }
+
+    /// See [`AsyncIoFdQueue::send_batch`]
+    pub async fn send_batch<B: AsRef<[u8]>>(&mut self, packets: &[B]) -> io::Result<usize> {
+        self.queue.send_batch(packets).await
+    }
+}
+#[cfg(feature = "async-io")]
+impl AsyncPacketQueueT for AsyncIoInterface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
+
+#[cfg(feature = "io-uring")]
+pub type UringInterface = LinuxInterface<UringFdQueue>;
+#[cfg(feature = "io-uring")]
+impl AsyncQueueT for UringInterface {}
+
+#[cfg(feature = "io-uring")]
+impl AsyncPacketQueueT for UringInterface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
+
 impl<Q: AsyncQueueT + Unpin> AsyncRead for LinuxInterface<Q> {
     delegate! {
         to Pin::new(&mut self.queue) {
             fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+            fn poll_read_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &mut [IoSliceMut<'_>]) -> Poll<io::Result<usize>>;
         }
     }
 }
@@ -99,8 +437,52 @@ impl<Q: AsyncQueueT + Unpin> AsyncWrite for LinuxInterface<Q> {
     delegate! {
         to Pin::new(&mut self.queue) {
             fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+            fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<io::Result<usize>>;
             fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
             fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
         }
     }
 }
+
+#[cfg(feature = "tokio")]
+impl<Q: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for LinuxInterface<Q> {
+    delegate! {
+        to Pin::new(&mut self.queue) {
+            fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<io::Result<()>>;
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Q: tokio::io::AsyncWrite + Unpin> tokio::io::AsyncWrite for LinuxInterface<Q> {
+    delegate! {
+        to Pin::new(&mut self.queue) {
+            fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+            fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<io::Result<usize>>;
+            fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+            fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+        }
+        to self.queue {
+            fn is_write_vectored(&self) -> bool;
+        }
+    }
+}
+
+impl<Q: AsRawFd> AsRawFd for LinuxInterface<Q> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.queue.as_raw_fd()
+    }
+}
+
+impl<Q: AsFd> AsFd for LinuxInterface<Q> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.queue.as_fd()
+    }
+}
+
+/// Interface is removed, when the returned descriptor is closed, unless it is persistent
+impl<Q: IntoRawFd> IntoRawFd for LinuxInterface<Q> {
+    fn into_raw_fd(self) -> RawFd {
+        self.queue.into_raw_fd()
+    }
+}
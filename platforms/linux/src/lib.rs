@@ -5,27 +5,141 @@
 //! Supported features:
 //! - TUN/TAP modes
 //! - Sync and async mode
+//! - io_uring-based async queues (`io-uring` feature)
+//! - async-io-based async queues for smol and async-std (`async-io` feature)
+//! - Registration in mio-based event loops (`mio` feature)
+//! - Multi-queue interfaces
+//! - Persistent interfaces
+//! - Interface owner and group assignment
+//! - Attaching to existing interfaces
+//! - Adopting TUN/TAP file descriptors, opened by another process
+//! - Creating interfaces in a privileged helper process (see [`helper`])
+//! - Packet information header (`IFF_PI`)
+//! - virtio-net header (`IFF_VNET_HDR`)
+//! - Checksum and segmentation offloads with userspace GSO/GRO helpers
 //!
 //! Low-level documentation for this driver can be found [here](https://www.kernel.org/doc/Documentation/networking/tuntap.txt).
 
+pub mod helper;
 mod interface;
+mod offload;
+mod packet_info;
 mod queue;
+mod vnet;
 
 use derive_builder::Builder;
+use helper::HelperClient;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tunio_core::traits::{DriverT, PlatformIfConfigT};
 use tunio_core::Error;
 
+#[cfg(feature = "async-io")]
+pub use interface::AsyncIoInterface;
+#[cfg(feature = "mio")]
+pub use interface::MioInterface;
 #[cfg(feature = "tokio")]
 pub use interface::TokioInterface;
+#[cfg(feature = "io-uring")]
+pub use interface::UringInterface;
 pub use interface::{Interface, LinuxInterface};
+pub use offload::{gro_coalesce, gso_split, Offload};
+pub use packet_info::{PacketInfo, TUN_PKT_STRIP};
+#[cfg(feature = "tokio")]
+pub use tunio_core::queue::tokiofd::{PacketSender, ReadHalf, WriteHalf};
+pub use vnet::VirtioNetHdr;
+
+pub struct Driver {
+    helper: Option<Arc<HelperClient>>,
+}
 
-pub struct Driver {}
+impl Driver {
+    /// Creates a driver, that asks a privileged helper to create interfaces.
+    ///
+    /// `stream` must be connected to a process, that runs [`helper::serve`].
+    pub fn with_helper(stream: UnixStream) -> Self {
+        Self {
+            helper: Some(Arc::new(HelperClient::new(stream))),
+        }
+    }
+}
 
 #[derive(Builder, Clone)]
-pub struct PlatformIfConfig {}
+pub struct PlatformIfConfig {
+    /// Create interface with multiple queues (`IFF_MULTI_QUEUE`).
+    ///
+    /// Additional queues are attached using [`LinuxInterface::open_queue`].
+    #[builder(default = "false")]
+    pub multi_queue: bool,
+    /// Keep interface after its file descriptors are closed (`TUNSETPERSIST`).
+    ///
+    /// Persistent interface can be removed using [`LinuxInterface::remove`].
+    #[builder(default = "false")]
+    pub persist: bool,
+    /// User, that is allowed to open this interface without `CAP_NET_ADMIN` (`TUNSETOWNER`).
+    ///
+    /// Useful with [`persist`](Self::persist) to hand over interface to an unprivileged process.
+    #[builder(default, setter(strip_option))]
+    pub owner: Option<libc::uid_t>,
+    /// Group, whose members are allowed to open this interface without `CAP_NET_ADMIN` (`TUNSETGROUP`).
+    #[builder(default, setter(strip_option))]
+    pub group: Option<libc::gid_t>,
+    /// Prepend packet information header to each packet (disables `IFF_NO_PI`).
+    ///
+    /// Use [`LinuxInterface::read_with_info`] and [`LinuxInterface::write_with_info`] to access
+    /// the header separately from the payload.
+    #[builder(default = "false")]
+    pub packet_info: bool,
+    /// Prepend virtio-net header to each packet (`IFF_VNET_HDR`).
+    ///
+    /// Use [`LinuxInterface::read_with_vnet_hdr`] and [`LinuxInterface::write_with_vnet_hdr`] to access
+    /// the header separately from the payload. Cannot be combined with [`packet_info`](Self::packet_info).
+    #[builder(default = "false")]
+    pub vnet_hdr: bool,
+    /// Offload features to request from the kernel. Requires [`vnet_hdr`](Self::vnet_hdr).
+    ///
+    /// With segmentation offloads enabled, the kernel passes GSO super-packets, that can be split
+    /// using [`gso_split`]. Outgoing packets can be merged using [`gro_coalesce`].
+    #[builder(default = "Offload::empty()")]
+    pub offload: Offload,
+    /// Path of the clone device, that is used to create interfaces.
+    ///
+    /// Useful in containers and chroots, where the device node is placed elsewhere.
+    #[builder(default = "PathBuf::from(queue::TUN_DEVICE_PATH)", setter(into))]
+    pub device_path: PathBuf,
+    /// Create the clone device node (`c 10 200`), if [`device_path`](Self::device_path) does not exist.
+    ///
+    /// Node is created in a private directory next to [`device_path`](Self::device_path), so its
+    /// filesystem must allow device nodes (not mounted with `nodev`), and removed right after
+    /// it is opened. Missing parent directories are created. Requires `CAP_MKNOD`.
+    #[builder(default = "false")]
+    pub create_device_node: bool,
+}
 
 impl PlatformIfConfigT for PlatformIfConfig {
     type Builder = PlatformIfConfigBuilder;
+
+    /// Applies the same rules as the kernel (`dev_valid_name`).
+    ///
+    /// Name can be a template with a single `%d`, that is replaced by the kernel.
+    /// Empty name is allowed as well, the kernel uses `tun%d` or `tap%d` for it.
+    fn validate_name(name: &str) -> Result<(), Error> {
+        // IFNAMSIZ includes the terminating NUL
+        if name.len() >= libc::IFNAMSIZ {
+            return Err(Error::InterfaceNameTooLong(name.len(), libc::IFNAMSIZ - 1));
+        }
+
+        let valid_chars = !name.contains(|c: char| c == '/' || c == ':' || c.is_whitespace());
+        let valid_template = match name.split_once('%') {
+            Some((_, rest)) => rest.starts_with('d') && !rest.contains('%'),
+            None => true,
+        };
+        match name.is_empty() || (name != "." && name != ".." && valid_chars && valid_template) {
+            true => Ok(()),
+            false => Err(Error::InterfaceNameInvalid),
+        }
+    }
 }
 
 impl Default for PlatformIfConfig {
@@ -38,6 +152,41 @@ impl DriverT for Driver {
     type PlatformIfConfig = PlatformIfConfig;
 
     fn new() -> Result<Self, Error> {
-        Ok(Self {})
+        Ok(Self { helper: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        for name in ["", "tun0", "tun%d", "%d", "my-tap.1", "a234567890abcde"] {
+            assert!(PlatformIfConfig::validate_name(name).is_ok(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        for name in [
+            ".", "..", "a/b", "a:b", "a b", "tun\t", "tun%s", "tun%d%d", "tun%",
+        ] {
+            assert!(
+                matches!(
+                    PlatformIfConfig::validate_name(name),
+                    Err(Error::InterfaceNameInvalid)
+                ),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn too_long_name() {
+        assert!(matches!(
+            PlatformIfConfig::validate_name("a234567890abcdef"),
+            Err(Error::InterfaceNameTooLong(16, 15))
+        ));
     }
 }
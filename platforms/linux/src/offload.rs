@@ -0,0 +1,685 @@
+use crate::VirtioNetHdr;
+use std::collections::HashMap;
+use std::io;
+use std::ops::{BitOr, BitOrAssign};
+
+/// Offload features, that can be requested from the kernel (`TUNSETOFFLOAD`).
+///
+/// Offloads are only usable together with [`vnet_hdr`](crate::PlatformIfConfig::vnet_hdr).
+/// Segmentation offloads require [`CSUM`](Self::CSUM) to be enabled as well.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct Offload(u32);
+
+impl Offload {
+    /// Checksum offload (`TUN_F_CSUM`)
+    pub const CSUM: Self = Self(0x01);
+    /// TCP segmentation offload for IPv4 (`TUN_F_TSO4`)
+    pub const TSO4: Self = Self(0x02);
+    /// TCP segmentation offload for IPv6 (`TUN_F_TSO6`)
+    pub const TSO6: Self = Self(0x04);
+    /// TCP segmentation offload with ECN bits (`TUN_F_TSO_ECN`)
+    pub const TSO_ECN: Self = Self(0x08);
+    /// UDP segmentation offload for IPv4 (`TUN_F_USO4`), Linux 6.2+
+    pub const USO4: Self = Self(0x20);
+    /// UDP segmentation offload for IPv6 (`TUN_F_USO6`), Linux 6.2+
+    pub const USO6: Self = Self(0x40);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    pub(crate) const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Offload {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Offload {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+const PROTO_TCP: u8 = libc::IPPROTO_TCP as u8;
+const PROTO_UDP: u8 = libc::IPPROTO_UDP as u8;
+
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const TCP_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+const TCP_CSUM_OFFSET: usize = 16;
+const UDP_CSUM_OFFSET: usize = 6;
+const MAX_PACKET_LEN: usize = u16::MAX as usize;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+const TCP_CWR: u8 = 0x80;
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Adds big-endian 16-bit words of `data` to ones' complement sum
+fn checksum_add(mut sum: u64, data: &[u8]) -> u64 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u64;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u64) << 8;
+    }
+    sum
+}
+
+fn checksum_fold(mut sum: u64) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+fn put_u16(packet: &mut [u8], offset: usize, value: u16) {
+    packet[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn get_u16(packet: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([packet[offset], packet[offset + 1]])
+}
+
+fn get_u32(packet: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        packet[offset],
+        packet[offset + 1],
+        packet[offset + 2],
+        packet[offset + 3],
+    ])
+}
+
+/// Sum of TCP/UDP pseudo-header for the IP packet
+fn pseudo_header_sum(packet: &[u8], protocol: u8, l4_len: usize) -> u64 {
+    let addresses = match packet[0] >> 4 {
+        4 => &packet[12..20],
+        _ => &packet[8..40],
+    };
+    checksum_add(0, addresses) + protocol as u64 + l4_len as u64
+}
+
+fn update_ipv4_checksum(packet: &mut [u8], header_len: usize) {
+    put_u16(packet, 10, 0);
+    let csum = !checksum_fold(checksum_add(0, &packet[..header_len]));
+    put_u16(packet, 10, csum);
+}
+
+/// Sets IP packet length fields to match the buffer size
+fn update_ip_length(packet: &mut [u8], ip_header_len: usize) {
+    let len = packet.len();
+    match packet[0] >> 4 {
+        4 => {
+            put_u16(packet, 2, len as u16);
+            update_ipv4_checksum(packet, ip_header_len);
+        }
+        _ => put_u16(packet, 4, (len - IPV6_HEADER_LEN) as u16),
+    }
+}
+
+/// Calculates full L4 checksum from scratch
+fn update_l4_checksum(packet: &mut [u8], protocol: u8, l4_start: usize, csum_offset: usize) {
+    let l4_len = packet.len() - l4_start;
+    put_u16(packet, l4_start + csum_offset, 0);
+
+    let sum = checksum_add(
+        pseudo_header_sum(packet, protocol, l4_len),
+        &packet[l4_start..],
+    );
+    let csum = match !checksum_fold(sum) {
+        0 if protocol == PROTO_UDP => 0xffff,
+        csum => csum,
+    };
+    put_u16(packet, l4_start + csum_offset, csum);
+}
+
+struct IpHeader {
+    header_len: usize,
+    protocol: u8,
+}
+
+fn parse_ip(packet: &[u8]) -> io::Result<IpHeader> {
+    match packet.first().map(|b| b >> 4) {
+        Some(4) if packet.len() >= IPV4_HEADER_LEN => {
+            let header_len = ((packet[0] & 0x0f) as usize) * 4;
+            if header_len < IPV4_HEADER_LEN || header_len > packet.len() {
+                return Err(invalid_data("invalid IPv4 header length"));
+            }
+            Ok(IpHeader {
+                header_len,
+                protocol: packet[9],
+            })
+        }
+        Some(6) if packet.len() >= IPV6_HEADER_LEN => Ok(IpHeader {
+            header_len: IPV6_HEADER_LEN,
+            protocol: packet[6],
+        }),
+        _ => Err(invalid_data("packet is not a valid IPv4 or IPv6 packet")),
+    }
+}
+
+/// Completes partial checksum (`VIRTIO_NET_HDR_F_NEEDS_CSUM`) of a non-GSO packet.
+fn complete_checksum(packet: &mut [u8], hdr: &VirtioNetHdr) -> io::Result<()> {
+    let start = hdr.csum_start as usize;
+    let field = start + hdr.csum_offset as usize;
+    if field + 2 > packet.len() {
+        return Err(invalid_data("checksum offset is out of packet bounds"));
+    }
+
+    // Checksum field is already seeded with the pseudo-header sum
+    let csum = match !checksum_fold(checksum_add(0, &packet[start..])) {
+        0 if hdr.csum_offset as usize == UDP_CSUM_OFFSET => 0xffff,
+        csum => csum,
+    };
+    put_u16(packet, field, csum);
+    Ok(())
+}
+
+/// Splits a packet, read with [`read_with_vnet_hdr`](crate::LinuxInterface::read_with_vnet_hdr),
+/// into ready-to-use IP packets.
+///
+/// GSO super-packets (TCP and UDP) are segmented into packets with at most `gso_size` bytes of payload,
+/// with IP and L4 headers and checksums fixed up for every segment. If packet is not a GSO packet,
+/// but its checksum is not calculated yet, the checksum is completed.
+///
+/// Packet must start with an IP header, so only L3 (TUN) interfaces are supported.
+pub fn gso_split(hdr: &VirtioNetHdr, packet: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    if !hdr.is_gso() {
+        let mut packet = packet.to_vec();
+        if hdr.needs_csum() {
+            complete_checksum(&mut packet, hdr)?;
+        }
+        return Ok(vec![packet]);
+    }
+
+    let ip = parse_ip(packet)?;
+    let l4_start = match hdr.needs_csum() {
+        true => hdr.csum_start as usize,
+        false => ip.header_len,
+    };
+
+    let (protocol, l4_header_len, csum_offset) = match hdr.gso_type & !VirtioNetHdr::GSO_ECN {
+        VirtioNetHdr::GSO_TCPV4 | VirtioNetHdr::GSO_TCPV6 => {
+            if packet.len() < l4_start + TCP_HEADER_LEN {
+                return Err(invalid_data("TCP header is truncated"));
+            }
+            let header_len = ((packet[l4_start + 12] >> 4) as usize) * 4;
+            if header_len < TCP_HEADER_LEN {
+                return Err(invalid_data("invalid TCP header length"));
+            }
+            (PROTO_TCP, header_len, TCP_CSUM_OFFSET)
+        }
+        VirtioNetHdr::GSO_UDP_L4 => (PROTO_UDP, UDP_HEADER_LEN, UDP_CSUM_OFFSET),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unsupported GSO type",
+            ))
+        }
+    };
+
+    let header_len = l4_start + l4_header_len;
+    let gso_size = hdr.gso_size as usize;
+    if header_len > packet.len() {
+        return Err(invalid_data("L4 header is truncated"));
+    }
+    if gso_size == 0 {
+        return Err(invalid_data("GSO size is zero"));
+    }
+
+    let (headers, payload) = packet.split_at(header_len);
+    // Packet without payload still produces a single segment with headers only
+    let count = ((payload.len() + gso_size - 1) / gso_size).max(1);
+    let mut segments = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let chunk = &payload[i * gso_size..payload.len().min((i + 1) * gso_size)];
+        let mut segment = Vec::with_capacity(header_len + chunk.len());
+        segment.extend_from_slice(headers);
+        segment.extend_from_slice(chunk);
+
+        if segment[0] >> 4 == 4 {
+            let id = get_u16(&segment, 4).wrapping_add(i as u16);
+            put_u16(&mut segment, 4, id);
+        }
+        update_ip_length(&mut segment, ip.header_len);
+
+        if protocol == PROTO_TCP {
+            let seq = get_u32(&segment, l4_start + 4).wrapping_add((i * gso_size) as u32);
+            segment[l4_start + 4..l4_start + 8].copy_from_slice(&seq.to_be_bytes());
+
+            let flags = &mut segment[l4_start + 13];
+            if i + 1 != count {
+                *flags &= !(TCP_FIN | TCP_PSH);
+            }
+            if i != 0 {
+                *flags &= !TCP_CWR;
+            }
+        } else {
+            let l4_len = segment.len() - l4_start;
+            put_u16(&mut segment, l4_start + 4, l4_len as u16);
+        }
+        update_l4_checksum(&mut segment, protocol, l4_start, csum_offset);
+
+        segments.push(segment);
+    }
+
+    Ok(segments)
+}
+
+/// Flow key: IP version, L4 protocol, addresses and ports
+type FlowKey = [u8; 38];
+
+struct Segment {
+    ip_header_len: usize,
+    protocol: u8,
+    header_len: usize,
+    payload_len: usize,
+    key: FlowKey,
+}
+
+fn parse_segment(packet: &[u8], offload: Offload) -> Option<Segment> {
+    let ip = parse_ip(packet).ok()?;
+    let version = packet[0] >> 4;
+
+    let allowed = match (version, ip.protocol) {
+        (4, PROTO_TCP) => offload.contains(Offload::CSUM | Offload::TSO4),
+        (6, PROTO_TCP) => offload.contains(Offload::CSUM | Offload::TSO6),
+        (4, PROTO_UDP) => offload.contains(Offload::CSUM | Offload::USO4),
+        (6, PROTO_UDP) => offload.contains(Offload::CSUM | Offload::USO6),
+        _ => false,
+    };
+    if !allowed {
+        return None;
+    }
+    // IPv4 options and fragments are not coalesced
+    if version == 4 && (ip.header_len != IPV4_HEADER_LEN || get_u16(packet, 6) & 0x3fff != 0) {
+        return None;
+    }
+
+    let l4_start = ip.header_len;
+    let header_len = match ip.protocol {
+        PROTO_TCP if packet.len() >= l4_start + TCP_HEADER_LEN => {
+            let flags = packet[l4_start + 13];
+            if flags & !TCP_PSH != TCP_ACK {
+                return None;
+            }
+            let tcp_header_len = ((packet[l4_start + 12] >> 4) as usize) * 4;
+            if tcp_header_len < TCP_HEADER_LEN {
+                return None;
+            }
+            l4_start + tcp_header_len
+        }
+        PROTO_UDP => l4_start + UDP_HEADER_LEN,
+        _ => return None,
+    };
+    if header_len >= packet.len() {
+        return None;
+    }
+
+    Some(Segment {
+        ip_header_len: ip.header_len,
+        protocol: ip.protocol,
+        header_len,
+        payload_len: packet.len() - header_len,
+        key: flow_key(packet, &ip)?,
+    })
+}
+
+/// Builds flow key of a TCP or UDP packet
+fn flow_key(packet: &[u8], ip: &IpHeader) -> Option<FlowKey> {
+    let l4_start = ip.header_len;
+    if !matches!(ip.protocol, PROTO_TCP | PROTO_UDP) || packet.len() < l4_start + 4 {
+        return None;
+    }
+
+    let mut key = [0u8; 38];
+    key[0] = packet[0] >> 4;
+    key[1] = ip.protocol;
+    let addresses = match key[0] {
+        4 => &packet[12..20],
+        _ => &packet[8..40],
+    };
+    key[2..2 + addresses.len()].copy_from_slice(addresses);
+    key[34..38].copy_from_slice(&packet[l4_start..l4_start + 4]);
+    Some(key)
+}
+
+struct Coalesced {
+    packet: Vec<u8>,
+    ip_header_len: usize,
+    protocol: u8,
+    header_len: usize,
+    gso_size: usize,
+    segments: usize,
+    next_seq: u32,
+    closed: bool,
+}
+
+impl Coalesced {
+    fn new(packet: &[u8], segment: &Segment) -> Self {
+        let next_seq = match segment.protocol {
+            PROTO_TCP => {
+                get_u32(packet, segment.ip_header_len + 4).wrapping_add(segment.payload_len as u32)
+            }
+            _ => 0,
+        };
+        let flags = packet.get(segment.ip_header_len + 13).copied();
+
+        Self {
+            packet: packet.to_vec(),
+            ip_header_len: segment.ip_header_len,
+            protocol: segment.protocol,
+            header_len: segment.header_len,
+            gso_size: segment.payload_len,
+            segments: 1,
+            next_seq,
+            closed: segment.protocol == PROTO_TCP && flags.unwrap_or(0) & TCP_PSH != 0,
+        }
+    }
+
+    fn can_append(&self, packet: &[u8], segment: &Segment) -> bool {
+        if self.closed
+            || segment.header_len != self.header_len
+            || segment.payload_len > self.gso_size
+            || self.packet.len() + segment.payload_len > MAX_PACKET_LEN
+        {
+            return false;
+        }
+
+        // IP fields, that must be the same in all segments
+        let ip_same = match packet[0] >> 4 {
+            // TOS, DF flag and TTL
+            4 => {
+                packet[1] == self.packet[1]
+                    && packet[6] & 0x40 == self.packet[6] & 0x40
+                    && packet[8] == self.packet[8]
+            }
+            // Traffic class, flow label and hop limit
+            _ => packet[..4] == self.packet[..4] && packet[7] == self.packet[7],
+        };
+        if !ip_same {
+            return false;
+        }
+
+        match self.protocol {
+            PROTO_TCP => {
+                let l4 = self.ip_header_len;
+                get_u32(packet, l4 + 4) == self.next_seq
+                    // Acknowledgment number
+                    && packet[l4 + 8..l4 + 12] == self.packet[l4 + 8..l4 + 12]
+                    // Window
+                    && packet[l4 + 14..l4 + 16] == self.packet[l4 + 14..l4 + 16]
+                    // Options
+                    && packet[l4 + TCP_HEADER_LEN..self.header_len]
+                        == self.packet[l4 + TCP_HEADER_LEN..self.header_len]
+            }
+            _ => true,
+        }
+    }
+
+    fn append(&mut self, packet: &[u8], segment: &Segment) {
+        self.packet.extend_from_slice(&packet[segment.header_len..]);
+        self.segments += 1;
+
+        // Only the last segment can be smaller than gso_size
+        if segment.payload_len < self.gso_size {
+            self.closed = true;
+        }
+        if self.protocol == PROTO_TCP {
+            self.next_seq = self.next_seq.wrapping_add(segment.payload_len as u32);
+            let psh = packet[segment.ip_header_len + 13] & TCP_PSH;
+            self.packet[self.ip_header_len + 13] |= psh;
+            if psh != 0 {
+                self.closed = true;
+            }
+        }
+    }
+
+    fn finish(mut self) -> (VirtioNetHdr, Vec<u8>) {
+        if self.segments == 1 {
+            return (VirtioNetHdr::default(), self.packet);
+        }
+
+        let l4_start = self.ip_header_len;
+        update_ip_length(&mut self.packet, self.ip_header_len);
+
+        let l4_len = self.packet.len() - l4_start;
+        let (gso_type, csum_offset) = match (self.packet[0] >> 4, self.protocol) {
+            (4, PROTO_TCP) => (VirtioNetHdr::GSO_TCPV4, TCP_CSUM_OFFSET),
+            (_, PROTO_TCP) => (VirtioNetHdr::GSO_TCPV6, TCP_CSUM_OFFSET),
+            _ => {
+                put_u16(&mut self.packet, l4_start + 4, l4_len as u16);
+                (VirtioNetHdr::GSO_UDP_L4, UDP_CSUM_OFFSET)
+            }
+        };
+
+        // Partial checksum: pseudo-header sum, that is completed by the kernel for every segment
+        let partial = checksum_fold(pseudo_header_sum(&self.packet, self.protocol, l4_len));
+        put_u16(&mut self.packet, l4_start + csum_offset, partial);
+
+        let hdr = VirtioNetHdr {
+            flags: VirtioNetHdr::F_NEEDS_CSUM,
+            gso_type,
+            hdr_len: self.header_len as u16,
+            gso_size: self.gso_size as u16,
+            csum_start: l4_start as u16,
+            csum_offset: csum_offset as u16,
+        };
+        (hdr, self.packet)
+    }
+}
+
+/// Coalesces consecutive TCP or UDP packets of the same flow into GSO super-packets, that can be
+/// written with [`write_with_vnet_hdr`](crate::LinuxInterface::write_with_vnet_hdr) in a single call.
+///
+/// Only packet kinds, that are enabled in `offload`, are coalesced. Other packets are passed
+/// through unchanged with an empty virtio-net header. Packets of the same flow keep their order.
+///
+/// Packets must start with an IP header, so only L3 (TUN) interfaces are supported.
+pub fn gro_coalesce<P: AsRef<[u8]>>(
+    packets: &[P],
+    offload: Offload,
+) -> Vec<(VirtioNetHdr, Vec<u8>)> {
+    let mut coalesced: Vec<Coalesced> = Vec::with_capacity(packets.len());
+    let mut flows: HashMap<FlowKey, usize> = HashMap::new();
+    let mut passthrough = Vec::new();
+
+    for packet in packets {
+        let packet = packet.as_ref();
+
+        let segment = match parse_segment(packet, offload) {
+            Some(segment) => segment,
+            None => {
+                // Following packets of the same flow must not be merged over this one
+                let key = parse_ip(packet).ok().and_then(|ip| flow_key(packet, &ip));
+                if let Some(&i) = key.as_ref().and_then(|key| flows.get(key)) {
+                    coalesced[i].closed = true;
+                }
+                passthrough.push((coalesced.len(), packet.to_vec()));
+                continue;
+            }
+        };
+
+        match flows.get(&segment.key) {
+            Some(&i) if coalesced[i].can_append(packet, &segment) => {
+                coalesced[i].append(packet, &segment)
+            }
+            _ => {
+                flows.insert(segment.key, coalesced.len());
+                coalesced.push(Coalesced::new(packet, &segment));
+            }
+        }
+    }
+
+    // Merge coalesced packets with passthrough ones, preserving their relative order
+    let mut result = Vec::with_capacity(coalesced.len() + passthrough.len());
+    let mut passthrough = passthrough.into_iter().peekable();
+    for (i, item) in coalesced.into_iter().enumerate() {
+        while let Some((_, packet)) = passthrough.next_if(|(pos, _)| *pos <= i) {
+            result.push((VirtioNetHdr::default(), packet));
+        }
+        result.push(item.finish());
+    }
+    result.extend(passthrough.map(|(_, packet)| (VirtioNetHdr::default(), packet)));
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: Offload = Offload(
+        Offload::CSUM.0 | Offload::TSO4.0 | Offload::TSO6.0 | Offload::USO4.0 | Offload::USO6.0,
+    );
+
+    /// Builds IP packet with valid lengths and checksums
+    fn packet(version: u8, protocol: u8, id: u16, l4_header: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut packet = match version {
+            4 => vec![
+                0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, protocol, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+            ],
+            _ => {
+                let mut header = vec![0x60, 0, 0, 0, 0, 0, protocol, 64];
+                header.extend_from_slice(&[0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+                header.extend_from_slice(&[0xfd, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+                header
+            }
+        };
+        let ip_header_len = packet.len();
+        if version == 4 {
+            put_u16(&mut packet, 4, id);
+        }
+        packet.extend_from_slice(l4_header);
+        packet.extend_from_slice(payload);
+
+        update_ip_length(&mut packet, ip_header_len);
+        let csum_offset = match protocol {
+            PROTO_TCP => TCP_CSUM_OFFSET,
+            _ => {
+                let l4_len = (l4_header.len() + payload.len()) as u16;
+                put_u16(&mut packet, ip_header_len + 4, l4_len);
+                UDP_CSUM_OFFSET
+            }
+        };
+        update_l4_checksum(&mut packet, protocol, ip_header_len, csum_offset);
+        packet
+    }
+
+    fn tcp(version: u8, id: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut header = vec![0x04, 0xd2, 0, 80];
+        header.extend_from_slice(&seq.to_be_bytes());
+        header.extend_from_slice(&[0, 0, 0, 1, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        packet(version, PROTO_TCP, id, &header, payload)
+    }
+
+    fn udp(version: u8, id: u16, payload: &[u8]) -> Vec<u8> {
+        packet(
+            version,
+            PROTO_UDP,
+            id,
+            &[0x04, 0xd2, 0, 53, 0, 0, 0, 0],
+            payload,
+        )
+    }
+
+    fn assert_round_trip(packets: &[Vec<u8>], gso_type: u8) {
+        let coalesced = gro_coalesce(packets, ALL);
+        assert_eq!(coalesced.len(), 1);
+
+        let (hdr, packet) = &coalesced[0];
+        assert_eq!(hdr.gso_type, gso_type);
+        assert_eq!(gso_split(hdr, packet).unwrap(), packets);
+    }
+
+    #[test]
+    fn tcp_round_trip() {
+        for (version, gso_type) in [(4, VirtioNetHdr::GSO_TCPV4), (6, VirtioNetHdr::GSO_TCPV6)] {
+            let packets = [
+                tcp(version, 7, 1, TCP_ACK, &[1; 100]),
+                tcp(version, 8, 101, TCP_ACK, &[2; 100]),
+                tcp(version, 9, 201, TCP_ACK | TCP_PSH, &[3; 40]),
+            ];
+            assert_round_trip(&packets, gso_type);
+        }
+    }
+
+    #[test]
+    fn udp_round_trip() {
+        for version in [4, 6] {
+            let packets = [
+                udp(version, 7, &[1; 100]),
+                udp(version, 8, &[2; 100]),
+                udp(version, 9, &[3; 40]),
+            ];
+            assert_round_trip(&packets, VirtioNetHdr::GSO_UDP_L4);
+        }
+    }
+
+    #[test]
+    fn coalesce_keeps_order_around_passthrough() {
+        let packets = [
+            tcp(4, 1, 1, TCP_ACK, &[1; 100]),
+            tcp(4, 2, 101, TCP_ACK, &[]),
+            tcp(4, 3, 101, TCP_ACK, &[2; 100]),
+        ];
+        let coalesced = gro_coalesce(&packets, ALL);
+
+        let result: Vec<_> = coalesced.into_iter().map(|(_, packet)| packet).collect();
+        assert_eq!(result, packets);
+    }
+
+    #[test]
+    fn invalid_tcp_header_length() {
+        let mut packet = tcp(4, 1, 1, TCP_ACK, &[1; 100]);
+        packet[IPV4_HEADER_LEN + 12] = 0x40;
+
+        let coalesced = gro_coalesce(&[&packet, &packet], ALL);
+        assert_eq!(coalesced.len(), 2);
+
+        let hdr = VirtioNetHdr {
+            gso_type: VirtioNetHdr::GSO_TCPV4,
+            gso_size: 50,
+            ..VirtioNetHdr::default()
+        };
+        let e = gso_split(&hdr, &packet).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn split_without_payload() {
+        let packet = tcp(4, 1, 1, TCP_ACK, &[]);
+        let hdr = VirtioNetHdr {
+            gso_type: VirtioNetHdr::GSO_TCPV4,
+            gso_size: 50,
+            ..VirtioNetHdr::default()
+        };
+        assert_eq!(gso_split(&hdr, &packet).unwrap(), [packet]);
+    }
+}
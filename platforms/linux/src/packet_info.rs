@@ -0,0 +1,131 @@
+use crate::LinuxInterface;
+use libc::IFF_NO_PI;
+use std::io::{self, IoSlice, IoSliceMut};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tunio_core::traits::{AsyncQueueT, SyncQueueT};
+
+/// Packet was truncated, because read buffer is too small.
+///
+/// Reads never return truncated packets, they fail with
+/// [`PacketTooLarge`](tunio_core::PacketTooLarge) instead, so this flag is not observed.
+pub const TUN_PKT_STRIP: u16 = 0x0001;
+
+/// Packet information header (`struct tun_pi`), that precedes each packet if
+/// [`packet_info`](crate::PlatformIfConfig::packet_info) is enabled.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct PacketInfo {
+    /// Packet flags. Only [`TUN_PKT_STRIP`] is defined by the kernel.
+    pub flags: u16,
+    /// Packet protocol, an EtherType value like `ETH_P_IP` or `ETH_P_IPV6`.
+    pub protocol: u16,
+}
+
+impl PacketInfo {
+    /// Size of header in bytes
+    pub const SIZE: usize = 4;
+
+    pub fn new(protocol: u16) -> Self {
+        Self { flags: 0, protocol }
+    }
+
+    pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        Self {
+            // Flags are in host byte order, protocol is in network byte order
+            flags: u16::from_ne_bytes([bytes[0], bytes[1]]),
+            protocol: u16::from_be_bytes([bytes[2], bytes[3]]),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let [f0, f1] = self.flags.to_ne_bytes();
+        let [p0, p1] = self.protocol.to_be_bytes();
+        [f0, f1, p0, p1]
+    }
+}
+
+fn read_result(header: [u8; PacketInfo::SIZE], n: usize) -> io::Result<(PacketInfo, usize)> {
+    match n.checked_sub(PacketInfo::SIZE) {
+        Some(len) => Ok((PacketInfo::from_bytes(header), len)),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "packet information header is truncated",
+        )),
+    }
+}
+
+fn write_result(n: usize) -> io::Result<usize> {
+    Ok(n.saturating_sub(PacketInfo::SIZE))
+}
+
+impl<Q> LinuxInterface<Q> {
+    fn check_packet_info(&self) -> io::Result<()> {
+        match self.flags & IFF_NO_PI {
+            0 => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet information header is disabled for this interface",
+            )),
+        }
+    }
+}
+
+impl<Q: SyncQueueT> LinuxInterface<Q> {
+    /// Reads a packet, returning its packet information header and payload size.
+    ///
+    /// Fails with [`PacketTooLarge`](tunio_core::PacketTooLarge) if the payload does not fit
+    /// into `buf`.
+    pub fn read_with_info(&mut self, buf: &mut [u8]) -> io::Result<(PacketInfo, usize)> {
+        self.check_packet_info()?;
+
+        let mut header = [0u8; PacketInfo::SIZE];
+        let n = self
+            .queue
+            .read_vectored(&mut [IoSliceMut::new(&mut header), IoSliceMut::new(buf)])?;
+        read_result(header, n)
+    }
+
+    /// Writes a packet with given packet information header, returning payload size.
+    pub fn write_with_info(&mut self, info: PacketInfo, buf: &[u8]) -> io::Result<usize> {
+        self.check_packet_info()?;
+
+        let header = info.to_bytes();
+        let n = self
+            .queue
+            .write_vectored(&[IoSlice::new(&header), IoSlice::new(buf)])?;
+        write_result(n)
+    }
+}
+
+impl<Q: AsyncQueueT> LinuxInterface<Q> {
+    /// Async version of [`read_with_info`](Self::read_with_info).
+    pub fn poll_read_with_info(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(PacketInfo, usize)>> {
+        self.check_packet_info()?;
+
+        let mut header = [0u8; PacketInfo::SIZE];
+        let n = ready!(Pin::new(&mut self.queue).poll_read_vectored(
+            cx,
+            &mut [IoSliceMut::new(&mut header), IoSliceMut::new(buf)]
+        ))?;
+        Poll::Ready(read_result(header, n))
+    }
+
+    /// Async version of [`write_with_info`](Self::write_with_info).
+    pub fn poll_write_with_info(
+        &mut self,
+        cx: &mut Context<'_>,
+        info: PacketInfo,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.check_packet_info()?;
+
+        let header = info.to_bytes();
+        let n = ready!(Pin::new(&mut self.queue)
+            .poll_write_vectored(cx, &[IoSlice::new(&header), IoSlice::new(buf)]))?;
+        Poll::Ready(write_result(n))
+    }
+}
@@ -1,16 +1,201 @@
 use crate::Error;
-use libc::{IFF_NO_PI, IFF_TAP, IFF_TUN};
+use crate::{Offload, PlatformIfConfig};
+use libc::{IFF_MULTI_QUEUE, IFF_NO_PI, IFF_TAP, IFF_TUN, IFF_VNET_HDR};
 use netconfig::sys::posix::ifreq::ifreq;
+use nix::errno::Errno;
+use nix::sys::stat::{makedev, Mode, SFlag};
+use nix::sys::statvfs::{statvfs, FsFlags};
+use std::ffi::{CString, OsString};
 use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
 use std::os::unix::fs::OpenOptionsExt;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
 use tunio_core::config::Layer;
+use tunio_core::traits::PlatformIfConfigT;
 
 mod ioctls {
     nix::ioctl_write_int!(tunsetiff, b'T', 202);
     nix::ioctl_write_int!(tunsetpersist, b'T', 203);
     nix::ioctl_write_int!(tunsetowner, b'T', 204);
     nix::ioctl_write_int!(tunsetgroup, b'T', 206);
+    nix::ioctl_write_int!(tunsetoffload, b'T', 208);
+    nix::ioctl_read!(tungetiff, b'T', 210, libc::c_uint);
+}
+
+/// Default location of the clone device, that is used to create TUN/TAP interfaces
+pub(crate) const TUN_DEVICE_PATH: &str = "/dev/net/tun";
+/// Major and minor numbers of the clone device
+const TUN_DEVICE_MAJOR: u64 = 10;
+const TUN_DEVICE_MINOR: u64 = 200;
+
+/// Clone device, that is used to create interfaces and attach queues to them
+#[derive(Clone)]
+pub(crate) struct CloneDevice {
+    pub path: PathBuf,
+    /// Create the device node, if `path` does not exist
+    pub create_node: bool,
+}
+
+impl CloneDevice {
+    pub fn new(platform: &PlatformIfConfig) -> Self {
+        Self {
+            path: platform.device_path.clone(),
+            create_node: platform.create_device_node,
+        }
+    }
+
+    fn open(&self, blocking: bool) -> Result<fs::File, Error> {
+        let mut open_opts = fs::OpenOptions::new();
+        open_opts.read(true).write(true);
+        if !blocking {
+            open_opts.custom_flags(libc::O_NONBLOCK);
+        }
+
+        match open_opts
+            .open(&self.path)
+            .map_err(|e| open_error(&self.path, e))
+        {
+            Err(Error::DeviceNodeMissing { .. }) if self.create_node => {
+                open_private_node(&self.path, &open_opts)
+            }
+            result => result,
+        }
+    }
+}
+
+impl Default for CloneDevice {
+    fn default() -> Self {
+        Self {
+            path: TUN_DEVICE_PATH.into(),
+            create_node: false,
+        }
+    }
+}
+
+/// Creates the clone device node in a private directory next to `path` and opens it.
+///
+/// Temporary directories are often mounted with `nodev`, so the node is placed on the filesystem,
+/// that is meant to hold it. Missing parent directories of `path` are created, like
+/// `mkdir -p /dev/net` does. Node is removed right after it is opened, the descriptor stays valid.
+fn open_private_node(path: &Path, open_opts: &fs::OpenOptions) -> Result<fs::File, Error> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(parent).map_err(|source| Error::OperationFailed {
+        operation: "create device directory",
+        source,
+    })?;
+
+    let template = parent.join(".tunio-XXXXXX");
+    let template =
+        CString::new(template.into_os_string().into_vec()).map_err(|e| Error::OperationFailed {
+            operation: "mkdtemp",
+            source: e.into(),
+        })?;
+    let template = template.into_raw();
+    // Directory is created with 0700 mode, so nobody else can open the node
+    let created = unsafe { libc::mkdtemp(template) };
+    let dir = PathBuf::from(OsString::from_vec(
+        unsafe { CString::from_raw(template) }.into_bytes(),
+    ));
+    if created.is_null() {
+        return Err(failed("mkdtemp")(io::Error::last_os_error()));
+    }
+
+    let node = dir.join("tun");
+    let result = nix::sys::stat::mknod(
+        &node,
+        SFlag::S_IFCHR,
+        Mode::S_IRUSR | Mode::S_IWUSR,
+        makedev(TUN_DEVICE_MAJOR, TUN_DEVICE_MINOR),
+    )
+    .map_err(|errno| match errno {
+        Errno::EPERM => Error::PermissionDenied {
+            operation: format!("mknod {}", node.display()),
+            capability: Some("CAP_MKNOD"),
+        },
+        errno => failed("mknod")(errno),
+    })
+    .and_then(|()| open_opts.open(&node).map_err(|e| open_error(path, e)));
+
+    let _ = fs::remove_file(&node);
+    let _ = fs::remove_dir(&dir);
+    result
+}
+
+/// Converts error of a system call into [`Error::OperationFailed`], naming the call.
+///
+/// All TUN/TAP ioctls fail with `EPERM` only if the caller lacks `CAP_NET_ADMIN`.
+fn failed<E: Into<io::Error>>(operation: &'static str) -> impl FnOnce(E) -> Error {
+    move |e| {
+        let source = e.into();
+        match source.raw_os_error() {
+            Some(libc::EPERM) => Error::PermissionDenied {
+                operation: operation.to_string(),
+                capability: Some("CAP_NET_ADMIN"),
+            },
+            _ => Error::OperationFailed { operation, source },
+        }
+    }
+}
+
+/// Explains, why the clone device could not be opened
+fn open_error(path: &Path, e: io::Error) -> Error {
+    match e.raw_os_error() {
+        Some(libc::ENOENT | libc::ENODEV | libc::ENXIO) => {
+            Error::DeviceNodeMissing { path: path.into() }
+        }
+        // Device nodes can not be opened on such filesystem, whatever the privileges are
+        Some(libc::EACCES) if on_nodev_mount(path) => Error::InvalidConfigValue {
+            name: "device_path".to_string(),
+            value: path.display().to_string(),
+            reason: "filesystem is mounted with nodev".to_string(),
+        },
+        // Usually caused by file mode of the node or by the device cgroup of a container
+        Some(libc::EACCES | libc::EPERM) => Error::PermissionDenied {
+            operation: format!("open {}", path.display()),
+            capability: None,
+        },
+        _ => Error::OperationFailed {
+            operation: "open clone device",
+            source: e,
+        },
+    }
+}
+
+/// Checks, whether `path` (or its directory, if `path` does not exist) is on a filesystem,
+/// mounted with `nodev`
+fn on_nodev_mount(path: &Path) -> bool {
+    statvfs(path)
+        .or_else(|e| match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => statvfs(parent),
+            _ => Err(e),
+        })
+        .map_or(false, |stat| stat.flags().contains(FsFlags::ST_NODEV))
+}
+
+/// Explains, why `TUNSETIFF` could not create or attach to interface `name`
+fn tunsetiff_error(name: &str, flags: libc::c_int, errno: Errno) -> Error {
+    match errno {
+        // Interface has a queue attached already and it is not a multi-queue interface
+        Errno::EBUSY => Error::NameInUse(name.to_string()),
+        // Interface exists, but its parameters differ from requested
+        Errno::EINVAL if !name.is_empty() && !name.contains('%') => {
+            match existing_device_flags(name) {
+                Ok(_) => match check_existing_device(name, flags) {
+                    Err(e) => e,
+                    Ok(()) => failed("TUNSETIFF")(errno),
+                },
+                // Name is taken by a device of another kind
+                Err(Error::InvalidConfigValue { .. }) => Error::NameInUse(name.to_string()),
+                Err(_) => failed("TUNSETIFF")(errno),
+            }
+        }
+        errno => failed("TUNSETIFF")(errno),
+    }
 }
 
 pub(crate) struct Device {
@@ -18,29 +203,189 @@ pub(crate) struct Device {
     pub name: String,
 }
 
-pub(crate) fn create_device(name: &str, layer: Layer, blocking: bool) -> Result<Device, Error> {
-    let mut open_opts = fs::OpenOptions::new();
-    open_opts.read(true).write(true);
-    if !blocking {
-        open_opts.custom_flags(libc::O_NONBLOCK);
+/// Makes interface persistent, so it is not destroyed after the last queue is closed
+pub(crate) fn set_persist(fd: RawFd, persist: bool) -> Result<(), Error> {
+    unsafe { ioctls::tunsetpersist(fd, persist as _) }.map_err(failed("TUNSETPERSIST"))?;
+    Ok(())
+}
+
+/// Sets user, that is allowed to attach to interface without `CAP_NET_ADMIN` (`TUNSETOWNER`)
+pub(crate) fn set_owner(fd: RawFd, owner: libc::uid_t) -> Result<(), Error> {
+    unsafe { ioctls::tunsetowner(fd, owner as _) }.map_err(failed("TUNSETOWNER"))?;
+    Ok(())
+}
+
+/// Sets group, that is allowed to attach to interface without `CAP_NET_ADMIN` (`TUNSETGROUP`)
+pub(crate) fn set_group(fd: RawFd, group: libc::gid_t) -> Result<(), Error> {
+    unsafe { ioctls::tunsetgroup(fd, group as _) }.map_err(failed("TUNSETGROUP"))?;
+    Ok(())
+}
+
+/// Enables offload features (`TUNSETOFFLOAD`)
+pub(crate) fn set_offload(fd: RawFd, offload: Offload) -> Result<(), Error> {
+    unsafe { ioctls::tunsetoffload(fd, offload.bits() as _) }.map_err(failed("TUNSETOFFLOAD"))?;
+    Ok(())
+}
+
+/// Queries name and flags of the interface, that the device is attached to (`TUNGETIFF`)
+pub(crate) fn device_info(fd: RawFd) -> Result<(String, libc::c_int), Error> {
+    let mut req = ifreq::new("");
+    unsafe { ioctls::tungetiff(fd, &mut req as *mut _ as _) }.map_err(failed("TUNGETIFF"))?;
+
+    // Only flags, that are set by `device_flags`, are kept, so they can be used to open more queues
+    let flags = unsafe { req.ifr_ifru.ifru_flags } as libc::c_int
+        & (IFF_TUN | IFF_TAP | IFF_NO_PI | IFF_MULTI_QUEUE | IFF_VNET_HDR);
+    let name =
+        String::try_from(req.ifr_ifrn).map_err(|e| Error::InterfaceNameError(format!("{e:?}")))?;
+    Ok((name, flags))
+}
+
+/// Switches the device into blocking or non-blocking mode (`O_NONBLOCK`)
+pub(crate) fn set_blocking(fd: RawFd, blocking: bool) -> Result<(), Error> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(failed("F_GETFL")(io::Error::last_os_error()));
+    }
+    let flags = match blocking {
+        true => flags & !libc::O_NONBLOCK,
+        false => flags | libc::O_NONBLOCK,
+    };
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+        return Err(failed("F_SETFL")(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Reads TUN/TAP flags of an existing interface from sysfs
+pub(crate) fn existing_device_flags(name: &str) -> Result<libc::c_int, Error> {
+    let iface_path = Path::new("/sys/class/net").join(name);
+    if !iface_path.exists() {
+        return Err(Error::InterfaceNotFound(name.to_string()));
     }
-    let tun_device = open_opts.open("/dev/net/tun")?;
 
-    let mut init_flags = match layer {
+    let flags = match fs::read_to_string(iface_path.join("tun_flags")) {
+        Ok(flags) => flags,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::InvalidConfigValue {
+                name: "name".to_string(),
+                value: name.to_string(),
+                reason: "interface is not a TUN/TAP device".to_string(),
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let flags = flags.trim();
+
+    libc::c_int::from_str_radix(flags.trim_start_matches("0x"), 16).map_err(|_| {
+        Error::InvalidConfigValue {
+            name: "tun_flags".to_string(),
+            value: flags.to_string(),
+            reason: "unexpected sysfs value".to_string(),
+        }
+    })
+}
+
+/// Checks, that an existing interface can be attached to with given flags
+pub(crate) fn check_existing_device(name: &str, flags: libc::c_int) -> Result<(), Error> {
+    if name.is_empty() || name.contains('%') {
+        return Err(Error::InterfaceNameInvalid);
+    }
+
+    let existing_flags = existing_device_flags(name)?;
+
+    let (expected, actual) = (device_layer(flags), device_layer(existing_flags));
+    if expected != actual {
+        return Err(Error::LayerMismatch { expected, actual });
+    }
+
+    if flags & IFF_MULTI_QUEUE != existing_flags & IFF_MULTI_QUEUE {
+        return Err(Error::InvalidConfigValue {
+            name: "multi_queue".to_string(),
+            value: (flags & IFF_MULTI_QUEUE != 0).to_string(),
+            reason: "does not match existing interface".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+fn device_layer(flags: libc::c_int) -> Layer {
+    match flags & IFF_TAP {
+        0 => Layer::L3,
+        _ => Layer::L2,
+    }
+}
+
+/// Computes TUNSETIFF flags for the given interface parameters
+pub(crate) fn device_flags(layer: Layer, platform: &PlatformIfConfig) -> libc::c_int {
+    let mut flags = match layer {
         Layer::L2 => IFF_TAP,
         Layer::L3 => IFF_TUN,
     };
-    init_flags |= IFF_NO_PI;
+    if !platform.packet_info {
+        flags |= IFF_NO_PI;
+    }
+    if platform.multi_queue {
+        flags |= IFF_MULTI_QUEUE;
+    }
+    if platform.vnet_hdr {
+        flags |= IFF_VNET_HDR;
+    }
+    flags
+}
+
+pub(crate) fn create_device(
+    clone_device: &CloneDevice,
+    name: &str,
+    flags: libc::c_int,
+    blocking: bool,
+) -> Result<Device, Error> {
+    // IfConfig can be constructed without the builder, so the name is checked again
+    PlatformIfConfig::validate_name(name)?;
+    let tun_device = clone_device.open(blocking)?;
 
     let mut req = ifreq::new(name);
-    req.ifr_ifru.ifru_flags = init_flags as _;
+    req.ifr_ifru.ifru_flags = flags as _;
+
+    unsafe { ioctls::tunsetiff(tun_device.as_raw_fd(), &req as *const _ as _) }
+        .map_err(|errno| tunsetiff_error(name, flags, errno))?;
 
-    unsafe { ioctls::tunsetiff(tun_device.as_raw_fd(), &req as *const _ as _) }.unwrap();
+    // Queues, attached to a multi-queue interface, that has queues already, keep flags of
+    // the interface instead of requested ones
+    let (name, actual_flags) = device_info(tun_device.as_raw_fd())?;
+    check_header_flags(flags, actual_flags)?;
 
     // Name can change due to formatting
     Ok(Device {
         device: tun_device,
-        name: String::try_from(req.ifr_ifrn)
-            .map_err(|e| Error::InterfaceNameError(format!("{e:?}")))?,
+        name,
     })
 }
+
+/// Checks, that packet headers, enabled on the device, are the requested ones
+fn check_header_flags(flags: libc::c_int, actual_flags: libc::c_int) -> Result<(), Error> {
+    let headers = [
+        (
+            "packet_info",
+            flags & IFF_NO_PI == 0,
+            actual_flags & IFF_NO_PI == 0,
+        ),
+        (
+            "vnet_hdr",
+            flags & IFF_VNET_HDR != 0,
+            actual_flags & IFF_VNET_HDR != 0,
+        ),
+    ];
+
+    match headers
+        .into_iter()
+        .find(|(_, requested, actual)| requested != actual)
+    {
+        Some((name, requested, _)) => Err(Error::InvalidConfigValue {
+            name: name.to_string(),
+            value: requested.to_string(),
+            reason: "does not match existing interface".to_string(),
+        }),
+        None => Ok(()),
+    }
+}
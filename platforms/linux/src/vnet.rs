@@ -0,0 +1,159 @@
+use crate::LinuxInterface;
+use libc::IFF_VNET_HDR;
+use std::io::{self, IoSlice, IoSliceMut};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tunio_core::traits::{AsyncQueueT, SyncQueueT};
+
+/// virtio-net header (`struct virtio_net_hdr`), that precedes each packet if
+/// [`vnet_hdr`](crate::PlatformIfConfig::vnet_hdr) is enabled.
+///
+/// It describes checksum and segmentation offload state of the packet.
+/// All fields are in host byte order.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct VirtioNetHdr {
+    pub flags: u8,
+    pub gso_type: u8,
+    /// Length of L2/L3/L4 headers
+    pub hdr_len: u16,
+    /// Size of segment payload, used for segmentation
+    pub gso_size: u16,
+    /// Offset from the packet start, where checksumming starts
+    pub csum_start: u16,
+    /// Offset from `csum_start`, where the checksum must be placed
+    pub csum_offset: u16,
+}
+
+impl VirtioNetHdr {
+    /// Size of header in bytes
+    pub const SIZE: usize = 10;
+
+    /// Checksum must be calculated starting from `csum_start` and placed at `csum_offset`
+    pub const F_NEEDS_CSUM: u8 = 1;
+    /// Checksum is already validated
+    pub const F_DATA_VALID: u8 = 2;
+
+    /// Packet is not a GSO super-packet
+    pub const GSO_NONE: u8 = 0;
+    pub const GSO_TCPV4: u8 = 1;
+    pub const GSO_UDP: u8 = 3;
+    pub const GSO_TCPV6: u8 = 4;
+    pub const GSO_UDP_L4: u8 = 5;
+    /// TCP ECN bit, combined with other GSO types
+    pub const GSO_ECN: u8 = 0x80;
+
+    pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        Self {
+            flags: bytes[0],
+            gso_type: bytes[1],
+            hdr_len: u16::from_ne_bytes([bytes[2], bytes[3]]),
+            gso_size: u16::from_ne_bytes([bytes[4], bytes[5]]),
+            csum_start: u16::from_ne_bytes([bytes[6], bytes[7]]),
+            csum_offset: u16::from_ne_bytes([bytes[8], bytes[9]]),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0] = self.flags;
+        bytes[1] = self.gso_type;
+        bytes[2..4].copy_from_slice(&self.hdr_len.to_ne_bytes());
+        bytes[4..6].copy_from_slice(&self.gso_size.to_ne_bytes());
+        bytes[6..8].copy_from_slice(&self.csum_start.to_ne_bytes());
+        bytes[8..10].copy_from_slice(&self.csum_offset.to_ne_bytes());
+        bytes
+    }
+
+    /// Returns `true` if packet is a GSO super-packet, that must be segmented
+    pub fn is_gso(&self) -> bool {
+        self.gso_type & !Self::GSO_ECN != Self::GSO_NONE
+    }
+
+    /// Returns `true` if packet checksum is not calculated yet
+    pub fn needs_csum(&self) -> bool {
+        self.flags & Self::F_NEEDS_CSUM != 0
+    }
+}
+
+fn read_result(header: [u8; VirtioNetHdr::SIZE], n: usize) -> io::Result<(VirtioNetHdr, usize)> {
+    match n.checked_sub(VirtioNetHdr::SIZE) {
+        Some(len) => Ok((VirtioNetHdr::from_bytes(header), len)),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "virtio-net header is truncated",
+        )),
+    }
+}
+
+fn write_result(n: usize) -> io::Result<usize> {
+    Ok(n.saturating_sub(VirtioNetHdr::SIZE))
+}
+
+impl<Q> LinuxInterface<Q> {
+    fn check_vnet_hdr(&self) -> io::Result<()> {
+        match self.flags & IFF_VNET_HDR {
+            0 => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "virtio-net header is disabled for this interface",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<Q: SyncQueueT> LinuxInterface<Q> {
+    /// Reads a packet, returning its virtio-net header and payload size.
+    pub fn read_with_vnet_hdr(&mut self, buf: &mut [u8]) -> io::Result<(VirtioNetHdr, usize)> {
+        self.check_vnet_hdr()?;
+
+        let mut header = [0u8; VirtioNetHdr::SIZE];
+        let n = self
+            .queue
+            .read_vectored(&mut [IoSliceMut::new(&mut header), IoSliceMut::new(buf)])?;
+        read_result(header, n)
+    }
+
+    /// Writes a packet with given virtio-net header, returning payload size.
+    pub fn write_with_vnet_hdr(&mut self, hdr: VirtioNetHdr, buf: &[u8]) -> io::Result<usize> {
+        self.check_vnet_hdr()?;
+
+        let header = hdr.to_bytes();
+        let n = self
+            .queue
+            .write_vectored(&[IoSlice::new(&header), IoSlice::new(buf)])?;
+        write_result(n)
+    }
+}
+
+impl<Q: AsyncQueueT> LinuxInterface<Q> {
+    /// Async version of [`read_with_vnet_hdr`](Self::read_with_vnet_hdr).
+    pub fn poll_read_with_vnet_hdr(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(VirtioNetHdr, usize)>> {
+        self.check_vnet_hdr()?;
+
+        let mut header = [0u8; VirtioNetHdr::SIZE];
+        let n = ready!(Pin::new(&mut self.queue).poll_read_vectored(
+            cx,
+            &mut [IoSliceMut::new(&mut header), IoSliceMut::new(buf)]
+        ))?;
+        Poll::Ready(read_result(header, n))
+    }
+
+    /// Async version of [`write_with_vnet_hdr`](Self::write_with_vnet_hdr).
+    pub fn poll_write_with_vnet_hdr(
+        &mut self,
+        cx: &mut Context<'_>,
+        hdr: VirtioNetHdr,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.check_vnet_hdr()?;
+
+        let header = hdr.to_bytes();
+        let n = ready!(Pin::new(&mut self.queue)
+            .poll_write_vectored(cx, &[IoSlice::new(&header), IoSlice::new(buf)]))?;
+        Poll::Ready(write_result(n))
+    }
+}
@@ -9,9 +9,11 @@ use std::task::{Context, Poll};
 use tunio_core::config::IfConfig;
 use tunio_core::queue::syncfd::SyncFdQueue;
 #[cfg(feature = "tokio")]
-use tunio_core::queue::tokiofd::TokioFdQueue;
+use tunio_core::queue::tokiofd::{PacketSender, ReadHalf, TokioFdQueue, WriteHalf};
 use tunio_core::queue::FdQueueT;
-use tunio_core::traits::{AsyncQueueT, InterfaceT, SyncQueueT};
+#[cfg(feature = "tokio")]
+use tunio_core::traits::AsyncPacketQueueT;
+use tunio_core::traits::{AsyncQueueT, InterfaceT, PacketQueueT, SyncQueueT};
 use tunio_core::Error;
 
 pub struct UtunInterface<Q> {
@@ -27,7 +29,7 @@ impl<Q: FdQueueT> InterfaceT for UtunInterface<Q> {
         _driver: &mut Self::PlatformDriver,
         params: IfConfig<Self::PlatformIfConfig>,
     ) -> Result<Self, Error> {
-        let queue = Q::new(create_device(&params.name, Q::BLOCKING)?);
+        let queue = Q::new(create_device(&params.name, Q::BLOCKING)?)?;
 
         Ok(Self {
             name: params.name,
@@ -45,7 +47,7 @@ impl<Q: FdQueueT> InterfaceT for UtunInterface<Q> {
     }
 
     fn up(&mut self) -> Result<(), Error> {
-        let handle = self.handle();
+        let handle = self.handle()?;
         handle.set_up(true)?;
         handle.set_running(true)?;
 
@@ -53,28 +55,49 @@ impl<Q: FdQueueT> InterfaceT for UtunInterface<Q> {
     }
 
     fn down(&mut self) -> Result<(), Error> {
-        let handle = self.handle();
+        let handle = self.handle()?;
         handle.set_up(false)?;
         handle.set_running(false)?;
 
         Ok(())
     }
 
-    fn handle(&self) -> netconfig::Interface {
-        netconfig::Interface::try_from_name(self.name()).unwrap()
+    fn handle(&self) -> Result<netconfig::Interface, Error> {
+        netconfig::Interface::try_from_name(self.name()).map_err(|e| match e {
+            netconfig::Error::InterfaceNotFound => Error::InterfaceNotFound(self.name.clone()),
+            e => e.into(),
+        })
     }
 }
 
+/// Each utun packet is prefixed with a 4-byte protocol family header
+const UTUN_HEADER_LEN: usize = 4;
+/// Largest IP packet
+const MAX_PAYLOAD_LEN: usize = u16::MAX as usize;
+
 impl<Q> UtunInterface<Q> {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Size of the largest packet, that the device can produce.
+    ///
+    /// It is not derived from the current MTU, so buffers stay large enough if MTU is raised later.
+    fn packet_size(&self) -> usize {
+        UTUN_HEADER_LEN + MAX_PAYLOAD_LEN
+    }
 }
 
 pub type Interface = UtunInterface<SyncFdQueue>;
 
 impl SyncQueueT for Interface {}
 
+impl PacketQueueT for Interface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
+
 impl<Q: SyncQueueT> Read for UtunInterface<Q> {
     delegate! {
         to self.queue {
@@ -96,6 +119,45 @@ impl<Q: SyncQueueT> Write for UtunInterface<Q> {
 pub type TokioInterface = UtunInterface<TokioFdQueue>;
 #[cfg(feature = "tokio")]
 impl AsyncQueueT for TokioInterface {}
+#[cfg(feature = "tokio")]
+impl TokioInterface {
+    /// Splits the interface into reading and writing halves, that can be used from different
+    /// tasks.
+    pub fn into_split(self) -> (ReadHalf, WriteHalf) {
+        self.queue.into_split()
+    }
+
+    /// See [`TokioFdQueue::sender`]
+    pub fn sender(&self) -> PacketSender {
+        self.queue.sender()
+    }
+
+    /// See [`TokioFdQueue::readable`]
+    pub async fn readable(&self) -> io::Result<()> {
+        self.queue.readable().await
+    }
+
+    /// See [`TokioFdQueue::writable`]
+    pub async fn writable(&self) -> io::Result<()> {
+        self.queue.writable().await
+    }
+
+    /// See [`TokioFdQueue::try_read`]
+    pub fn try_read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.queue.try_read(buf)
+    }
+
+    /// See [`TokioFdQueue::try_write`]
+    pub fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        self.queue.try_write(buf)
+    }
+}
+#[cfg(feature = "tokio")]
+impl AsyncPacketQueueT for TokioInterface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
 
 impl<Q: AsyncQueueT> AsyncRead for UtunInterface<Q> {
     delegate! {
@@ -114,3 +176,23 @@ impl<Q: AsyncQueueT> AsyncWrite for UtunInterface<Q> {
         }
     }
 }
+
+#[cfg(feature = "tokio")]
+impl<Q: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for UtunInterface<Q> {
+    delegate! {
+        to Pin::new(&mut self.queue) {
+            fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<io::Result<()>>;
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<Q: tokio::io::AsyncWrite + Unpin> tokio::io::AsyncWrite for UtunInterface<Q> {
+    delegate! {
+        to Pin::new(&mut self.queue) {
+            fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+            fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+            fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+        }
+    }
+}
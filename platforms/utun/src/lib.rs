@@ -8,6 +8,8 @@ mod queue;
 pub use interface::Interface;
 #[cfg(feature = "tokio")]
 pub use interface::TokioInterface;
+#[cfg(feature = "tokio")]
+pub use tunio_core::queue::tokiofd::{PacketSender, ReadHalf, WriteHalf};
 
 pub struct Driver {}
 
@@ -24,6 +26,14 @@ pub struct PlatformIfConfig {}
 
 impl PlatformIfConfigT for PlatformIfConfig {
     type Builder = PlatformIfConfigBuilder;
+
+    /// utun interfaces are always named `utunN`
+    fn validate_name(name: &str) -> Result<(), Error> {
+        match name.strip_prefix("utun").map(str::parse::<u32>) {
+            Some(Ok(_)) => Ok(()),
+            _ => Err(Error::InterfaceNameInvalid),
+        }
+    }
 }
 
 impl Default for PlatformIfConfig {
@@ -2,11 +2,20 @@ use crate::Error;
 use libc::{PF_SYSTEM, SYSPROTO_CONTROL};
 use nix::sys::socket::SysControlAddr;
 use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io;
 use std::mem;
 use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
 
 const UTUN_CONTROL_NAME: &str = "com.apple.net.utun_control";
 
+/// Converts error of a system call into [`Error::OperationFailed`], naming the call
+fn failed<E: Into<io::Error>>(operation: &'static str) -> impl FnOnce(E) -> Error {
+    move |e| Error::OperationFailed {
+        operation,
+        source: e.into(),
+    }
+}
+
 pub(crate) fn create_device(name: &str, blocking: bool) -> Result<OwnedFd, Error> {
     let mut id = match name {
         s if s.starts_with("utun") => s[4..].parse().map_err(|_| Error::InterfaceNameInvalid),
@@ -19,9 +28,10 @@ pub(crate) fn create_device(name: &str, blocking: bool) -> Result<OwnedFd, Error
         Type::DGRAM,
         Some(Protocol::from(SYSPROTO_CONTROL)),
     )
-    .unwrap();
+    .map_err(failed("socket(PF_SYSTEM)"))?;
 
-    let sa = SysControlAddr::from_name(tun_device.as_raw_fd(), UTUN_CONTROL_NAME, id).unwrap();
+    let sa = SysControlAddr::from_name(tun_device.as_raw_fd(), UTUN_CONTROL_NAME, id)
+        .map_err(failed("CTLIOCGINFO"))?;
 
     let (_, sa) = unsafe {
         SockAddr::init(|sa_storage, len| {
@@ -30,12 +40,11 @@ pub(crate) fn create_device(name: &str, blocking: bool) -> Result<OwnedFd, Error
             *len = mem::size_of::<libc::sockaddr_ctl>() as _;
             Ok(())
         })
-    }
-    .unwrap();
+    }?;
     if !blocking {
         tun_device.set_nonblocking(true)?;
     }
-    tun_device.connect(&sa).unwrap();
+    tun_device.connect(&sa).map_err(failed("connect"))?;
 
     Ok(unsafe { OwnedFd::from_raw_fd(tun_device.into_raw_fd()) })
 }
@@ -1,12 +1,34 @@
-use super::async_queue::AsyncQueue;
+use super::async_queue::{AsyncQueue, ReadHalf, WriteHalf};
 use super::interface::CommonInterface;
 use futures::{AsyncRead, AsyncWrite};
-use std::io::{self};
+use std::io::{self, ErrorKind};
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use tunio_core::traits::{AsyncPacketQueueT, AsyncQueueT};
 
 pub type AsyncInterface = CommonInterface<AsyncQueue>;
 
+impl AsyncQueueT for AsyncInterface {}
+
+impl AsyncInterface {
+    /// Splits the interface into reading and writing halves, that can be used from different
+    /// tasks.
+    ///
+    /// Interface must be up. Its session is ended when both halves are dropped.
+    pub fn into_split(mut self) -> io::Result<(ReadHalf, WriteHalf)> {
+        match self.queue.take() {
+            Some(queue) => Ok(queue.into_split()),
+            None => Err(ErrorKind::BrokenPipe.into()),
+        }
+    }
+}
+
+impl AsyncPacketQueueT for AsyncInterface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
+
 impl AsyncRead for AsyncInterface {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -26,26 +26,51 @@ enum ReadState {
 }
 
 pub struct AsyncQueue {
-    session: Session,
+    read: ReadHalf,
+    write: WriteHalf,
+}
+
+/// Reading half of an [`AsyncQueue`], created by [`AsyncQueue::into_split`].
+pub struct ReadHalf {
+    session: Arc<Session>,
 
     read_state: ReadState,
     shutdown_event: Arc<SafeEvent>,
 }
 
+/// Writing half of an [`AsyncQueue`], created by [`AsyncQueue::into_split`].
+pub struct WriteHalf {
+    session: Arc<Session>,
+}
+
 impl SessionQueueT for AsyncQueue {
     fn new(session: Session) -> Self {
+        let session = Arc::new(session);
+
         Self {
-            session,
+            read: ReadHalf {
+                session: session.clone(),
 
-            read_state: ReadState::Idle,
+                read_state: ReadState::Idle,
 
-            // Manual reset, because we use this event once and it must fire on all threads
-            shutdown_event: Arc::new(SafeEvent::new(true, false)),
+                // Manual reset, because we use this event once and it must fire on all threads
+                shutdown_event: Arc::new(SafeEvent::new(true, false)),
+            },
+            write: WriteHalf { session },
         }
     }
 }
 
-impl Drop for AsyncQueue {
+impl AsyncQueue {
+    /// Splits the queue into reading and writing halves, that can be used from different tasks.
+    ///
+    /// Session is ended when both halves are dropped.
+    pub fn into_split(self) -> (ReadHalf, WriteHalf) {
+        (self.read, self.write)
+    }
+}
+
+impl Drop for ReadHalf {
     fn drop(&mut self) {
         self.shutdown_event.set_event();
     }
@@ -72,7 +97,7 @@ fn wait_for_read(read_event: HANDLE, shutdown_event: Arc<SafeEvent>) -> WaitingS
     }
 }
 
-impl AsyncRead for AsyncQueue {
+impl AsyncRead for ReadHalf {
     fn poll_read(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -93,7 +118,7 @@ impl AsyncRead for AsyncQueue {
                         return Poll::Pending;
                     }
                 }
-                ReadState::Idle => match self.session.read(buf) {
+                ReadState::Idle => match (&*self.session).read(buf) {
                     Ok(n) => return Poll::Ready(Ok(n)),
                     Err(e) => {
                         if e.kind() == io::ErrorKind::WouldBlock {
@@ -115,14 +140,14 @@ impl AsyncRead for AsyncQueue {
     }
 }
 
-impl AsyncWrite for AsyncQueue {
+impl AsyncWrite for WriteHalf {
     // Write to wintun is already nonblocking
     fn poll_write(
-        mut self: Pin<&mut Self>,
+        self: Pin<&mut Self>,
         _cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        Poll::Ready(self.session.write(buf))
+        Poll::Ready((&*self.session).write(buf))
     }
 
     fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
@@ -135,3 +160,31 @@ impl AsyncWrite for AsyncQueue {
         Poll::Ready(Ok(()))
     }
 }
+
+impl AsyncRead for AsyncQueue {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.read).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for AsyncQueue {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.write).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.write).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.write).poll_close(cx)
+    }
+}
@@ -7,7 +7,7 @@ use std::io;
 use std::io::{ErrorKind, Read, Write};
 use std::sync::Arc;
 use tunio_core::config::{IfConfig, Layer};
-use tunio_core::traits::InterfaceT;
+use tunio_core::traits::{InterfaceT, PacketQueueT, SyncQueueT};
 use tunio_core::Error;
 use windows::core::GUID;
 use windows::Win32::NetworkManagement::IpHelper::ConvertInterfaceLuidToIndex;
@@ -66,17 +66,25 @@ impl<Q: SessionQueueT> InterfaceT for CommonInterface<Q> {
         Ok(())
     }
 
-    fn handle(&self) -> netconfig::Interface {
+    fn handle(&self) -> Result<netconfig::Interface, Error> {
         let mut index = 0;
         let luid = NET_LUID_LH {
             Value: self.adapter.luid(),
         };
 
-        unsafe {
-            ConvertInterfaceLuidToIndex(&luid, &mut index).unwrap();
-        }
-
-        netconfig::Interface::try_from_index(index).unwrap()
+        unsafe { ConvertInterfaceLuidToIndex(&luid, &mut index) }.map_err(|e| {
+            Error::OperationFailed {
+                operation: "ConvertInterfaceLuidToIndex",
+                source: e.into(),
+            }
+        })?;
+
+        netconfig::Interface::try_from_index(index).map_err(|e| match e {
+            netconfig::Error::InterfaceNotFound => {
+                Error::InterfaceNotFound(self.config.name.clone())
+            }
+            e => e.into(),
+        })
     }
 }
 
@@ -87,10 +95,26 @@ impl<Q: SessionQueueT> CommonInterface<Q> {
             None => Err(ErrorKind::BrokenPipe.into()),
         }
     }
+
+    /// Size of the largest packet, that the device can produce (`WINTUN_MAX_IP_PACKET_SIZE`).
+    ///
+    /// Wintun passes bare IP packets without any additional headers. Size is not derived from
+    /// the current MTU, so buffers stay large enough if MTU is raised later.
+    pub(crate) fn packet_size(&self) -> usize {
+        u16::MAX as usize
+    }
 }
 
 pub type Interface = CommonInterface<Queue>;
 
+impl SyncQueueT for Interface {}
+
+impl PacketQueueT for Interface {
+    fn max_packet_size(&self) -> io::Result<usize> {
+        Ok(self.packet_size())
+    }
+}
+
 impl Read for Interface {
     delegate::delegate! {
         to self.inner_queue_mut()? {
@@ -15,4 +15,4 @@ mod async_interface;
 mod async_queue;
 
 pub use async_interface::AsyncInterface;
-pub use async_queue::AsyncQueue;
+pub use async_queue::{AsyncQueue, ReadHalf, WriteHalf};
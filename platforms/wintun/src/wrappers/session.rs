@@ -1,11 +1,10 @@
 use super::Adapter;
 use super::HandleWrapper;
-use bytes::BufMut;
 use log::error;
 use std::io;
 use std::io::{Read, Write};
 use std::sync::Arc;
-use tunio_core::Error;
+use tunio_core::{Error, PacketTooLarge};
 use windows::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_NO_MORE_ITEMS, HANDLE, WIN32_ERROR};
 use wintun_sys::{WINTUN_MAX_RING_CAPACITY, WINTUN_MIN_RING_CAPACITY, WINTUN_SESSION_HANDLE};
 
@@ -56,6 +55,8 @@ impl<'a> Drop for PacketReader<'a> {
 pub struct Session {
     handle: HandleWrapper<WINTUN_SESSION_HANDLE>,
     wintun: Arc<wintun_sys::wintun>,
+    // Session must be ended before its adapter is closed
+    _adapter: Arc<Adapter>,
 }
 
 impl Session {
@@ -77,6 +78,7 @@ impl Session {
         Ok(Self {
             handle: HandleWrapper(session_handle),
             wintun,
+            _adapter: adapter,
         })
     }
 
@@ -101,13 +103,22 @@ impl Session {
     }
 }
 
-impl Read for Session {
-    fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
+// Wintun functions are thread-safe, so the session can be shared between a reader and a writer
+impl Read for &Session {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let packet = PacketReader::read(self.handle.clone(), &self.wintun);
         match packet {
             Ok(packet) => {
                 let packet_slice = packet.as_slice();
-                buf.put(packet_slice);
+                if packet_slice.len() > buf.len() {
+                    // Packet is released on drop
+                    return Err(PacketTooLarge {
+                        required: Some(packet_slice.len()),
+                        capacity: buf.len(),
+                    }
+                    .into());
+                }
+                buf[..packet_slice.len()].copy_from_slice(packet_slice);
                 Ok(packet_slice.len())
             }
             Err(e) => match error_eq(&e, ERROR_NO_MORE_ITEMS) {
@@ -118,7 +129,7 @@ impl Read for Session {
     }
 }
 
-impl Write for Session {
+impl Write for &Session {
     // does not block, as WintunAllocateSendPacket and WintunSendPacket are executed right one ofter another
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         let packet = unsafe {
@@ -146,6 +157,22 @@ impl Write for Session {
     }
 }
 
+impl Read for Session {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for Session {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
 impl Drop for Session {
     fn drop(&mut self) {
         unsafe {
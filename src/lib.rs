@@ -5,6 +5,8 @@ pub mod platform;
 pub use tunio_core::config::*;
 pub use tunio_core::Error;
 
+#[cfg(feature = "codec")]
+pub use tunio_core::codec;
 pub use tunio_core::config;
 pub use tunio_core::traits;
 
@@ -18,6 +20,8 @@ cfg_if::cfg_if! {
         pub type DefaultInterface = platform::linux::Interface;
         #[cfg(feature = "tokio")]
         pub type DefaultAsyncInterface = platform::linux::TokioInterface;
+        #[cfg(all(feature = "async-io", not(feature = "tokio")))]
+        pub type DefaultAsyncInterface = platform::linux::AsyncIoInterface;
     }else if #[cfg(target_os = "macos")] {
         pub type DefaultDriver = platform::utun::Driver;
         pub type DefaultInterface = platform::utun::Interface;